@@ -125,4 +125,84 @@ Compilation.add("Compilation: Finch", () => {
     console.log('Fastest is ' + this.filter('fastest').map('name'));
 })
 
-.run();
\ No newline at end of file
+.run();
+
+const products = [];
+for (let i = 0; i < 100; i++) {
+    products.push({ name: `Product ${i}`, price: i * 3, tags: ["new", "sale", "popular"], inStock: i % 3 !== 0 });
+}
+
+Finch.addTemplate("test_large", `
+<table>
+{{#each products product}}
+    <tr>
+        <td>{{product.name}}</td>
+        <td>{{product.price}}</td>
+        <td>{{#each product.tags tag}}<span>{{tag}}</span>{{/}}</td>
+        {{#if product.inStock}}
+            <td>In stock</td>
+        {{/#else}}
+            <td>Sold out</td>
+        {{/}}
+    </tr>
+{{/}}
+</table>
+`);
+
+const largehb = Handlebars.compile(`
+<table>
+{{#each products}}
+    <tr>
+        <td>{{name}}</td>
+        <td>{{price}}</td>
+        <td>{{#each tags}}<span>{{this}}</span>{{/each}}</td>
+        {{#if inStock}}
+            <td>In stock</td>
+        {{else}}
+            <td>Sold out</td>
+        {{/if}}
+    </tr>
+{{/each}}
+</table>
+`);
+
+const largeejs = Eta.compile(`
+<table>
+<% for (const product of it.products) { %>
+    <tr>
+        <td><%= product.name %></td>
+        <td><%= product.price %></td>
+        <td><% for (const tag of product.tags) { %><span><%= tag %></span><% } %></td>
+        <% if (product.inStock) { %>
+            <td>In stock</td>
+        <% } else { %>
+            <td>Sold out</td>
+        <% } %>
+    </tr>
+<% } %>
+</table>
+`);
+
+const LargeCompilation = new Benchmark.Suite("Large templates");
+
+LargeCompilation.add("Compilation: Finch - large template", () => {
+    Finch.compile("test_large", { products });
+})
+
+.add("Compilation: Handlebars - large template", () => {
+    largehb({ products });
+})
+
+.add("Compilation: Eta - large template", () => {
+    largeejs({ products }, Eta.getConfig());
+})
+
+.on('cycle', function(event) {
+    console.log(String(event.target));
+})
+
+.on('complete', function() {
+    console.log('Fastest is ' + this.filter('fastest').map('name'));
+})
+
+.run();
@@ -0,0 +1,317 @@
+use finch_core::Compiler;
+use finch_core::error::{Diagnostic, Severity};
+use finch_core::format::{self, FormatOptions};
+use finch_core::optimize::optimize;
+use finch_core::parser::{Parser, ParserOptions};
+use finch_core::transpiler::{self, TranspileOptions};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process;
+
+static USAGE: &str = "Usage: finch <command> [options]
+
+Commands:
+    lint <paths...>     Report every syntax error and warning in the templates. Directories are
+                        searched recursively for files with one of the --ext extensions.
+    ast <file>          Print the syntax tree of a template as JSON
+    fmt <paths...>      Format templates in place
+    precompile <paths...>
+                        Write the templates to a bundle, which the node.js package loads with
+                        loadBundle without parsing them. Templates are named like with render.
+    js <file>           Compile a template to a javascript module which renders it without node
+    render <file> [paths...]
+                        Render a template and print the result. The other templates in the paths can
+                        be used with the template helper, they're named after their file name
+                        without the extension, so two templates can't have the same file name.
+
+Options:
+    --ext <list>        Comma separated template extensions for directories (default: finch,html)
+    --bigint            Parse integer literals as BigInts
+    --indent <n>        fmt: re-indent multi-line block bodies by n spaces
+    --check             fmt: don't write anything, fail if a template isn't formatted
+    --compact           ast: print the JSON on a single line
+    --data <file>       render: JSON file with the data for the template
+    --out <file>        precompile: where to write the bundle (default: templates.bundle)
+    --commonjs          js: export the render function with module.exports
+
+Exit codes: 0 on success, 1 if a template has errors or isn't formatted, 2 on invalid usage or IO errors.";
+
+struct Args {
+    command: String,
+    paths: Vec<PathBuf>,
+    extensions: Vec<String>,
+    parser: ParserOptions,
+    indent: Option<usize>,
+    check: bool,
+    compact: bool,
+    commonjs: bool,
+    data: Option<PathBuf>,
+    out: PathBuf
+}
+
+impl Args {
+
+    fn parse() -> Result<Self, String> {
+        let mut raw = std::env::args().skip(1);
+        let command = raw.next().ok_or_else(|| String::from("Missing command"))?;
+        let mut args = Self {
+            command,
+            paths: vec![],
+            extensions: vec![String::from("finch"), String::from("html")],
+            parser: ParserOptions::default(),
+            indent: None,
+            check: false,
+            compact: false,
+            commonjs: false,
+            data: None,
+            out: PathBuf::from("templates.bundle")
+        };
+        while let Some(arg) = raw.next() {
+            match arg.as_str() {
+                "--ext" => {
+                    let list = raw.next().ok_or_else(|| String::from("Missing value for --ext"))?;
+                    args.extensions = list.split(',').map(|ext| ext.trim_start_matches('.').to_string()).collect();
+                },
+                "--indent" => {
+                    let width = raw.next().ok_or_else(|| String::from("Missing value for --indent"))?;
+                    args.indent = Some(width.parse().map_err(|_| format!("Invalid indent: {}", width))?);
+                },
+                "--data" => args.data = Some(PathBuf::from(raw.next().ok_or_else(|| String::from("Missing value for --data"))?)),
+                "--out" => args.out = PathBuf::from(raw.next().ok_or_else(|| String::from("Missing value for --out"))?),
+                "--bigint" => args.parser.bigint_integers = true,
+                "--check" => args.check = true,
+                "--compact" => args.compact = true,
+                "--commonjs" => args.commonjs = true,
+                "-h" | "--help" => args.command = String::from("help"),
+                other if other.starts_with("--") => return Err(format!("Unknown option {}", other)),
+                _ => args.paths.push(PathBuf::from(arg))
+            }
+        }
+        Ok(args)
+    }
+
+    // All template files in the given paths, directories are searched recursively
+    fn files(&self) -> Result<Vec<PathBuf>, String> {
+        if self.paths.is_empty() {
+            return Err(String::from("Expected at least one path"));
+        }
+        let mut res = vec![];
+        for path in &self.paths {
+            if path.is_dir() {
+                self.collect_dir(path, &mut res)?;
+            } else {
+                res.push(path.clone());
+            }
+        }
+        Ok(res)
+    }
+
+    fn collect_dir(&self, dir: &Path, res: &mut Vec<PathBuf>) -> Result<(), String> {
+        let mut entries = std::fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()
+            .map_err(|err| format!("{}: {}", dir.display(), err))?;
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                self.collect_dir(&path, res)?;
+            } else if path.extension().map(|ext| self.extensions.iter().any(|allowed| ext == allowed.as_str())).unwrap_or(false) {
+                res.push(path);
+            }
+        }
+        Ok(())
+    }
+
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn print_diagnostic(path: &Path, source: &str, diagnostic: &Diagnostic) {
+    let (line, column) = diagnostic.line_col(source);
+    match diagnostic.severity {
+        Severity::Error => eprintln!("{}:{}:{}: {}", path.display(), line, column, diagnostic.error),
+        Severity::Warning => eprintln!("{}:{}:{}: warning: {}", path.display(), line, column, diagnostic.error)
+    }
+}
+
+fn lint(args: &Args) -> Result<bool, String> {
+    let files = args.files()?;
+    let (mut errors, mut warnings) = (0, 0);
+    for path in &files {
+        let source = read(path)?;
+        let (mut ast, mut diagnostics) = Parser::parse_recovering(&source, args.parser);
+        errors += diagnostics.len();
+        let found = optimize(&mut ast);
+        warnings += found.len();
+        diagnostics.extend(found);
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        for diagnostic in &diagnostics {
+            print_diagnostic(path, &source, diagnostic);
+        }
+    }
+    eprintln!("Checked {} template(s), found {} error(s) and {} warning(s)", files.len(), errors, warnings);
+    Ok(errors == 0)
+}
+
+fn ast(args: &Args) -> Result<bool, String> {
+    let path = match args.paths.as_slice() {
+        [path] => path,
+        _ => return Err(String::from("Expected exactly one file"))
+    };
+    let source = read(path)?;
+    let (ast, diagnostics) = Parser::parse_recovering(&source, args.parser);
+    for diagnostic in &diagnostics {
+        print_diagnostic(path, &source, diagnostic);
+    }
+    let json = if args.compact { serde_json::to_string(&ast) } else { serde_json::to_string_pretty(&ast) };
+    println!("{}", json.map_err(|err| err.to_string())?);
+    Ok(diagnostics.is_empty())
+}
+
+fn fmt(args: &Args) -> Result<bool, String> {
+    let options = FormatOptions { parser: args.parser, indent: args.indent };
+    let mut ok = true;
+    for path in &args.files()? {
+        let source = read(path)?;
+        let formatted = match format::format(&source, options) {
+            Ok(formatted) => formatted,
+            Err(_) => {
+                // Report the error with its position
+                for diagnostic in &Parser::parse_recovering(&source, args.parser).1 {
+                    print_diagnostic(path, &source, diagnostic);
+                }
+                ok = false;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if args.check {
+            eprintln!("{}: not formatted", path.display());
+            ok = false;
+        } else {
+            std::fs::write(path, formatted).map_err(|err| format!("{}: {}", path.display(), err))?;
+            eprintln!("Formatted {}", path.display());
+        }
+    }
+    Ok(ok)
+}
+
+fn js(args: &Args) -> Result<bool, String> {
+    let path = match args.paths.as_slice() {
+        [path] => path,
+        _ => return Err(String::from("Expected exactly one file"))
+    };
+    let source = read(path)?;
+    match transpiler::transpile(&source, TranspileOptions { parser: args.parser, commonjs: args.commonjs }) {
+        Ok(res) => {
+            print!("{}", res);
+            Ok(true)
+        },
+        Err(_) => {
+            for diagnostic in &Parser::parse_recovering(&source, args.parser).1 {
+                print_diagnostic(path, &source, diagnostic);
+            }
+            Ok(false)
+        }
+    }
+}
+
+fn template_name(path: &Path) -> String {
+    path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+// Adds all templates in the paths, None if one of them has errors. Templates in different
+// directories can't have the same name, one of them would replace the other
+fn load_templates(args: &Args) -> Result<Option<Compiler>, String> {
+    let mut compiler = Compiler::new();
+    let mut ok = true;
+    let mut names: HashMap<String, &Path> = HashMap::new();
+    let files = args.files()?;
+    for path in &files {
+        let name = template_name(path);
+        if let Some(other) = names.get(&name) {
+            // The template given to render can be in one of the directories too
+            if matches!((other.canonicalize(), path.canonicalize()), (Ok(left), Ok(right)) if left == right) {
+                continue;
+            }
+            return Err(format!("{} and {} are both named {}, rename one of them", other.display(), path.display(), name));
+        }
+        names.insert(name.clone(), path);
+        let source = read(path)?;
+        if compiler.add_template(&name, &source, args.parser).is_err() {
+            for diagnostic in &Parser::parse_recovering(&source, args.parser).1 {
+                print_diagnostic(path, &source, diagnostic);
+            }
+            ok = false;
+        }
+    }
+    Ok(if ok { Some(compiler) } else { None })
+}
+
+fn render(args: &Args) -> Result<bool, String> {
+    let main = args.paths.first().ok_or_else(|| String::from("Expected a template"))?;
+    let compiler = match load_templates(args)? {
+        Some(compiler) => compiler,
+        None => return Ok(false)
+    };
+    let data = match &args.data {
+        Some(path) => serde_json::from_str(&read(path)?).map_err(|err| format!("{}: {}", path.display(), err))?,
+        None => serde_json::Value::Object(serde_json::Map::new())
+    };
+    match compiler.render_json(&template_name(main), &data) {
+        Ok(res) => {
+            print!("{}", res);
+            Ok(true)
+        },
+        Err(err) => {
+            eprintln!("{}: {}", main.display(), err);
+            Ok(false)
+        }
+    }
+}
+
+fn precompile(args: &Args) -> Result<bool, String> {
+    let compiler = match load_templates(args)? {
+        Some(compiler) => compiler,
+        None => return Ok(false)
+    };
+    let bundle = compiler.bundle().map_err(|err| err.to_string())?;
+    std::fs::write(&args.out, bundle).map_err(|err| format!("{}: {}", args.out.display(), err))?;
+    eprintln!("Wrote {} template(s) to {}", compiler.templates.len(), args.out.display());
+    Ok(true)
+}
+
+fn main() {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    let res = match args.command.as_str() {
+        "lint" => lint(&args),
+        "ast" => ast(&args),
+        "fmt" => fmt(&args),
+        "render" => render(&args),
+        "js" => js(&args),
+        "precompile" => precompile(&args),
+        "help" => {
+            println!("{}", USAGE);
+            Ok(true)
+        },
+        other => Err(format!("Unknown command {}\n\n{}", other, USAGE))
+    };
+    match res {
+        Ok(true) => {},
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    }
+}
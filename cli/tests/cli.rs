@@ -0,0 +1,97 @@
+use finch_core::Compiler;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// A fresh directory with the given files, every test gets its own
+fn dir_with(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("finch-cli-{}-{}", std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (name, contents) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+fn finch(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_finch")).current_dir(dir).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn lint_exit_codes() {
+    let dir = dir_with("lint", &[("ok.finch", "{{#if a}}{{a}}{{/}}"), ("warn/w.html", "{{#if true}}x{{/}}"), ("bad/b.finch", "{{a.}} {{#if x}}"), ("bad/skipped.txt", "{{")]);
+    let ok = finch(&dir, &["lint", "ok.finch", "warn"]);
+    assert_eq!(ok.status.code(), Some(0), "{}", stderr(&ok));
+    // Warnings are reported, but don't fail
+    assert!(stderr(&ok).contains("w.html:1:"), "{}", stderr(&ok));
+    assert!(stderr(&ok).contains("Checked 2 template(s), found 0 error(s) and 1 warning(s)"), "{}", stderr(&ok));
+    let bad = finch(&dir, &["lint", "bad"]);
+    assert_eq!(bad.status.code(), Some(1));
+    assert!(stderr(&bad).contains("b.finch:1:5: Expected property name after dot (.)"), "{}", stderr(&bad));
+    assert!(stderr(&bad).contains("Checked 1 template(s), found 2 error(s)"), "{}", stderr(&bad));
+    assert_eq!(finch(&dir, &["lint", "missing.finch"]).status.code(), Some(2));
+    assert_eq!(finch(&dir, &["lint"]).status.code(), Some(2));
+    assert_eq!(finch(&dir, &["lint", "--nope", "ok.finch"]).status.code(), Some(2));
+}
+
+#[test]
+fn fmt_writes_in_place() {
+    let dir = dir_with("fmt", &[("a.finch", "{{ user.name }} {{#if  a==1 }}one{{/}}"), ("b.finch", "{{x}}"), ("broken.finch", "{{a.}}")]);
+    let check = finch(&dir, &["fmt", "--check", "a.finch", "b.finch"]);
+    assert_eq!(check.status.code(), Some(1));
+    assert!(stderr(&check).contains("a.finch: not formatted"), "{}", stderr(&check));
+    assert_eq!(std::fs::read_to_string(dir.join("a.finch")).unwrap(), "{{ user.name }} {{#if  a==1 }}one{{/}}");
+    let write = finch(&dir, &["fmt", "a.finch", "b.finch"]);
+    assert_eq!(write.status.code(), Some(0), "{}", stderr(&write));
+    assert_eq!(std::fs::read_to_string(dir.join("a.finch")).unwrap(), "{{user.name}} {{#if a == 1}}one{{/}}");
+    assert!(!stderr(&write).contains("b.finch"), "{}", stderr(&write));
+    assert_eq!(finch(&dir, &["fmt", "--check", "a.finch"]).status.code(), Some(0));
+    // Templates with errors are reported and left alone
+    assert_eq!(finch(&dir, &["fmt", "broken.finch"]).status.code(), Some(1));
+    assert_eq!(std::fs::read_to_string(dir.join("broken.finch")).unwrap(), "{{a.}}");
+}
+
+#[test]
+fn precompile_writes_a_bundle() {
+    let dir = dir_with("precompile", &[("page.finch", "<h1>{{title}}</h1>{{#template \"row\" item /}}"), ("parts/row.html", "<li>{{name}}</li>")]);
+    let output = finch(&dir, &["precompile", "page.finch", "parts", "--out", "out.bundle"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Wrote 2 template(s) to out.bundle"), "{}", stderr(&output));
+    let mut compiler = Compiler::new();
+    compiler.load_bundle(&std::fs::read(dir.join("out.bundle")).unwrap()).unwrap();
+    let data = serde_json::json!({ "title": "Hi", "item": { "name": "Bob" } });
+    assert_eq!(compiler.render_json("page", &data).unwrap(), "<h1>Hi</h1><li>Bob</li>");
+}
+
+#[test]
+fn render_prints_the_result() {
+    let dir = dir_with("render", &[("page.finch", "{{#each users u}}{{#template \"user\" u /}}{{/}}"), ("user.finch", "[{{name}}]"), ("data.json", "{\"users\": [{\"name\": \"A\"}, {\"name\": \"B\"}]}"), ("fail.finch", "{{a.b.c}}")]);
+    let output = finch(&dir, &["render", "page.finch", "user.finch", "--data", "data.json"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "[A][B]");
+    // The main template can also be among the paths
+    assert_eq!(stdout(&finch(&dir, &["render", "page.finch", ".", "--ext", "finch", "--data", "data.json"])), "[A][B]");
+    let failed = finch(&dir, &["render", "fail.finch"]);
+    assert_eq!(failed.status.code(), Some(1));
+    assert!(stderr(&failed).starts_with("fail.finch: "), "{}", stderr(&failed));
+}
+
+#[test]
+fn templates_with_the_same_name_are_rejected() {
+    let dir = dir_with("names", &[("page.finch", "{{#template \"row\" /}}"), ("a/row.finch", "a"), ("b/row.html", "b")]);
+    for command in ["render", "precompile"] {
+        let output = finch(&dir, &[command, "page.finch", "a", "b"]);
+        assert_eq!(output.status.code(), Some(2), "{}", command);
+        assert!(stderr(&output).contains("are both named row, rename one of them"), "{}", stderr(&output));
+    }
+    assert!(!dir.join("templates.bundle").exists());
+}
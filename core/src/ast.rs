@@ -0,0 +1,248 @@
+//! The syntax tree produced by [Parser](crate::parser::Parser).
+//!
+//! All positions are byte offsets in the template source. Every node can be serialized
+//! and deserialized with serde, enums are written as `{"type": "Variant", "value": ...}`.
+
+use std::ops::Range;
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de::Error};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum BinaryOps {
+    /// `left == right`
+    Compare(ExpressionKind, ExpressionKind), 
+    /// `left != right`
+    Not(ExpressionKind, ExpressionKind),
+    /// `left === right`
+    StrictCompare(ExpressionKind, ExpressionKind),
+    /// `left !== right`
+    StrictNot(ExpressionKind, ExpressionKind),
+    /// `left > right`
+    Gt(ExpressionKind, ExpressionKind),
+    /// `left < right`
+    Lt(ExpressionKind, ExpressionKind),
+    /// `left >= right`
+    Gte(ExpressionKind, ExpressionKind),
+    /// `left <= right`
+    Lte(ExpressionKind, ExpressionKind),
+    /// `left && right`
+    And(ExpressionKind, ExpressionKind),
+    /// `left || right`
+    Or(ExpressionKind, ExpressionKind),
+    /// `left + right`
+    Add(ExpressionKind, ExpressionKind),
+    /// `left - right`
+    Sub(ExpressionKind, ExpressionKind),
+    /// `left * right`
+    Mul(ExpressionKind, ExpressionKind),
+    /// `left / right`
+    Div(ExpressionKind, ExpressionKind),
+    /// `left % right`
+    Rem(ExpressionKind, ExpressionKind)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum UnaryOps {
+    /// `!value`
+    Not(ExpressionKind),
+    /// `-value`, negative number literals are folded into [ExpressionKind::Number]
+    Neg(ExpressionKind)
+}
+
+/// Number literals compare by what was written, so `NaN` equals `NaN` and `-0` doesn't equal `0`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum ExpressionKind {
+    /// A variable without any property access (`name`)
+    Var(String),
+    /// A path expression (`user.name`, `items.0`), always has at least two parts
+    VarDot(Vec<String>),
+    /// `NaN`, `Infinity` and `-Infinity` are serialized as those strings, JSON has no numbers for them
+    Number(#[serde(serialize_with = "serialize_number", deserialize_with = "deserialize_number")] f64),
+    /// Serialized as a decimal string, so JSON readers don't lose precision
+    BigInt(#[serde(serialize_with = "serialize_bigint", deserialize_with = "deserialize_bigint")] i128),
+    String(String),
+    Bool(bool),
+    Undefined,
+    Null,
+    Binary(Box<BinaryOps>),
+    Unary(Box<UnaryOps>),
+    /// `var(...params)`
+    Call {
+        var: Box<ExpressionKind>,
+        params: Vec<ExpressionKind>
+    }
+}
+
+/// A helper block: `{{#name ...params}}block{{/}}`, or `{{#name ...params /}}` without a body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FnBlock {
+    pub name: String,
+    pub params: Vec<ExpressionKind>,
+    /// Named arguments, `key=value`, in the order they were written
+    pub hash: Vec<(String, ExpressionKind)>,
+    pub block: Option<SubText>,
+    /// The followup block, `{{/#name ...}}`
+    pub chain: Option<Box<FnBlock>>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum TemplateKind {
+    /// Static text between tags
+    Text(String),
+    /// `{{expression}}`
+    Expression(ExpressionKind),
+    Block(FnBlock),
+    /// A tag which failed to parse, only produced by [Parser::parse_recovering](crate::parser::Parser::parse_recovering)
+    Error
+}
+
+/// A single node of a template. For blocks, `pos` covers everything up to the end of the
+/// closing `{{/}}`, including followups.
+///
+/// Nodes compare equal when their contents are equal, positions are ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub pos: Range<usize>,
+    pub kind: TemplateKind
+}
+
+/// A sequence of nodes: either a whole template, or the body of a block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubText {
+    pub pos: Range<usize>,
+    pub templates: Vec<Template>
+}
+
+impl PartialEq for ExpressionKind {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Var(left), Self::Var(right)) => left == right,
+            (Self::VarDot(left), Self::VarDot(right)) => left == right,
+            (Self::Number(left), Self::Number(right)) => left.to_bits() == right.to_bits() || (left.is_nan() && right.is_nan()),
+            (Self::BigInt(left), Self::BigInt(right)) => left == right,
+            (Self::String(left), Self::String(right)) => left == right,
+            (Self::Bool(left), Self::Bool(right)) => left == right,
+            (Self::Undefined, Self::Undefined) | (Self::Null, Self::Null) => true,
+            (Self::Binary(left), Self::Binary(right)) => left == right,
+            (Self::Unary(left), Self::Unary(right)) => left == right,
+            (Self::Call { var, params }, Self::Call { var: other_var, params: other_params }) => var == other_var && params == other_params,
+            _ => false
+        }
+    }
+}
+
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl PartialEq for SubText {
+    fn eq(&self, other: &Self) -> bool {
+        self.templates == other.templates
+    }
+}
+
+fn serialize_number<S: Serializer>(num: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if num.is_nan() {
+        serializer.serialize_str("NaN")
+    } else if num.is_infinite() {
+        serializer.serialize_str(if *num > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        serializer.serialize_f64(*num)
+    }
+}
+
+fn deserialize_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Finite(f64),
+        Named(String)
+    }
+    match Number::deserialize(deserializer)? {
+        Number::Finite(num) => Ok(num),
+        Number::Named(name) => match name.as_str() {
+            "NaN" => Ok(f64::NAN),
+            "Infinity" => Ok(f64::INFINITY),
+            "-Infinity" => Ok(f64::NEG_INFINITY),
+            _ => Err(D::Error::custom(format!("invalid number {}", name)))
+        }
+    }
+}
+
+fn serialize_bigint<S: Serializer>(num: &i128, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(num)
+}
+
+fn deserialize_bigint<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i128, D::Error> {
+    String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+}
+
+impl BinaryOps {
+
+    pub fn operands(&self) -> (&ExpressionKind, &ExpressionKind) {
+        match self {
+            Self::Compare(left, right) | Self::Not(left, right) | Self::StrictCompare(left, right) | Self::StrictNot(left, right) |
+            Self::Gt(left, right) | Self::Lt(left, right) | Self::Gte(left, right) | Self::Lte(left, right) |
+            Self::And(left, right) | Self::Or(left, right) |
+            Self::Add(left, right) | Self::Sub(left, right) | Self::Mul(left, right) | Self::Div(left, right) | Self::Rem(left, right) => (left, right)
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> (&mut ExpressionKind, &mut ExpressionKind) {
+        match self {
+            Self::Compare(left, right) | Self::Not(left, right) | Self::StrictCompare(left, right) | Self::StrictNot(left, right) |
+            Self::Gt(left, right) | Self::Lt(left, right) | Self::Gte(left, right) | Self::Lte(left, right) |
+            Self::And(left, right) | Self::Or(left, right) |
+            Self::Add(left, right) | Self::Sub(left, right) | Self::Mul(left, right) | Self::Div(left, right) | Self::Rem(left, right) => (left, right)
+        }
+    }
+
+}
+
+impl UnaryOps {
+
+    pub fn operand(&self) -> &ExpressionKind {
+        match self {
+            Self::Not(exp) | Self::Neg(exp) => exp
+        }
+    }
+
+    pub fn operand_mut(&mut self) -> &mut ExpressionKind {
+        match self {
+            Self::Not(exp) | Self::Neg(exp) => exp
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_finite_numbers_round_trip_through_json() {
+        for (num, json) in [(f64::NAN, "\"NaN\""), (f64::INFINITY, "\"Infinity\""), (f64::NEG_INFINITY, "\"-Infinity\""), (1.5, "1.5")] {
+            let serialized = serde_json::to_string(&ExpressionKind::Number(num)).unwrap();
+            assert_eq!(serialized, format!("{{\"type\":\"Number\",\"value\":{}}}", json));
+            match serde_json::from_str(&serialized).unwrap() {
+                ExpressionKind::Number(back) => assert!(back == num || (back.is_nan() && num.is_nan()), "{}", serialized),
+                other => panic!("{:?}", other)
+            }
+        }
+        assert!(serde_json::from_str::<ExpressionKind>("{\"type\":\"Number\",\"value\":\"nope\"}").is_err());
+    }
+
+    #[test]
+    fn number_literals_compare_by_what_was_written() {
+        assert_eq!(ExpressionKind::Number(f64::NAN), ExpressionKind::Number(f64::NAN));
+        assert_ne!(ExpressionKind::Number(-0.0), ExpressionKind::Number(0.0));
+        assert_eq!(ExpressionKind::Number(1.5), ExpressionKind::Number(1.5));
+        assert_ne!(ExpressionKind::Number(1.0), ExpressionKind::BigInt(1));
+    }
+
+}
@@ -0,0 +1,111 @@
+//! Precompiled templates. A bundle stores the parsed templates together with their source,
+//! so they can be loaded without parsing them again.
+//!
+//! The format is the [MAGIC] bytes, the [VERSION] as a little-endian `u32`, and the
+//! templates encoded as CBOR.
+
+use serde::{Serialize, Deserialize};
+use crate::ast::SubText;
+use crate::compiler::Compiler;
+use crate::error::{FinchError, FinchResult};
+
+pub const MAGIC: &[u8; 6] = b"FINCHB";
+
+/// Increased whenever the syntax tree changes, bundles with a different version can't be loaded.
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct BundledTemplate {
+    pub name: String,
+    pub source: String,
+    pub ast: SubText
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Bundle {
+    pub templates: Vec<BundledTemplate>
+}
+
+impl Bundle {
+
+    /// A bundle with all templates of the compiler, sorted by name.
+    pub fn from_compiler(compiler: &Compiler) -> Self {
+        let mut templates = compiler.templates.iter().map(|(name, template)| BundledTemplate {
+            name: name.clone(),
+            source: template.source.clone(),
+            ast: template.ast.clone()
+        }).collect::<Vec<BundledTemplate>>();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { templates }
+    }
+
+    pub fn encode(&self) -> FinchResult<Vec<u8>> {
+        let mut res = MAGIC.to_vec();
+        res.extend_from_slice(&VERSION.to_le_bytes());
+        ciborium::ser::into_writer(self, &mut res).map_err(|err| FinchError::InvalidBundle(err.to_string()))?;
+        Ok(res)
+    }
+
+    pub fn decode(bytes: &[u8]) -> FinchResult<Self> {
+        if bytes.len() < MAGIC.len() + 4 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(FinchError::InvalidBundle(String::from("not a finch bundle")));
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&bytes[MAGIC.len()..MAGIC.len() + 4]);
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(FinchError::InvalidBundle(format!("the bundle has format version {}, but this version of finch only supports version {}. Precompile the templates again", version, VERSION)));
+        }
+        ciborium::de::from_reader(&bytes[MAGIC.len() + 4..]).map_err(|err| FinchError::InvalidBundle(err.to_string()))
+    }
+
+}
+
+impl Compiler {
+
+    /// Adds all templates of a bundle, replacing templates with the same name.
+    pub fn load_bundle(&mut self, bytes: &[u8]) -> FinchResult<()> {
+        for template in Bundle::decode(bytes)?.templates {
+            self.insert_template(template.name, template.source, template.ast);
+        }
+        Ok(())
+    }
+
+    /// Encodes all templates as a bundle.
+    pub fn bundle(&self) -> FinchResult<Vec<u8>> {
+        Bundle::from_compiler(self).encode()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserOptions;
+
+    fn bundle() -> Vec<u8> {
+        let mut compiler = Compiler::new();
+        compiler.add_template("hello", "Hello {{name}} {{-Infinity}} {{NaN}}", ParserOptions::default()).unwrap();
+        compiler.bundle().unwrap()
+    }
+
+    #[test]
+    fn loads_bundled_templates() {
+        let mut compiler = Compiler::new();
+        compiler.load_bundle(&bundle()).unwrap();
+        assert_eq!(compiler.render_json("hello", &serde_json::json!({ "name": "Bob" })).unwrap(), "Hello Bob -Infinity NaN");
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut bytes = bundle();
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        match Bundle::decode(&bytes) {
+            Err(FinchError::InvalidBundle(reason)) => assert!(reason.contains(&format!("format version {}", VERSION + 1)), "{}", reason),
+            _ => panic!("A bundle with another version was loaded")
+        }
+        assert!(matches!(Bundle::decode(b"FINCH"), Err(FinchError::InvalidBundle(_))));
+        assert!(matches!(Bundle::decode(b"NOTABUNDLE"), Err(FinchError::InvalidBundle(_))));
+    }
+
+}
@@ -0,0 +1,342 @@
+
+use crate::parser::*;
+use crate::ast::*;
+use crate::error::{FinchError, FinchResult};
+use crate::value::*;
+use crate::memory::*;
+use std::collections::HashMap;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::default_helpers;
+use crate::helper::{Bindings, Helper, HelperTable, JsonHelper};
+use crate::optimize::optimize;
+use crate::program::Program;
+
+/// A template which was added to a [Compiler].
+pub struct Template {
+    pub source: String,
+    pub ast: SubText,
+    /// What the template is rendered with
+    pub program: Program
+}
+
+pub struct Compiler {
+    pub templates: HashMap<String, Template>,
+    pub helpers: HelperTable
+}
+
+pub struct CompilerContext<'a> {
+    pub compiler: &'a Compiler,
+    /// The program of the template which is being rendered
+    pub program: &'a Program,
+    pub source: &'a mut dyn DataSource,
+    pub cache: Memory,
+    pub data: RawValue,
+    pub original: &'a str
+}
+
+impl Compiler {
+
+    pub fn new() -> Self {
+        let mut res = Self { 
+            templates: HashMap::new(),
+            helpers: HelperTable::default()
+        };
+        default_helpers::init(&mut res);
+        res
+    }
+
+    pub fn add_template(&mut self, name: &str, text: &str, options: ParserOptions) -> FinchResult<()> {
+        let parsed = Parser::parse(text, options)?;
+        self.insert_template(name.to_string(), text.to_string(), parsed);
+        Ok(())
+    }
+
+    /// Optimizes and lowers a parsed template, then adds it.
+    pub(crate) fn insert_template(&mut self, name: String, source: String, mut ast: SubText) {
+        optimize(&mut ast);
+        let program = Program::lower(&ast, &mut self.helpers);
+        self.templates.insert(name, Template { source, ast, program });
+    }
+
+    /// Adds a helper, replacing any helper with the same name.
+    pub fn register_helper<H: Helper + 'static>(&mut self, name: &str, helper: H) {
+        self.helpers.insert(name, Box::new(helper));
+    }
+
+    /// Adds a helper which sets variables for its body, see [Bindings]. Templates are lowered with
+    /// the variables, so the ones which use them have to be added after the helper.
+    pub fn register_binding_helper<H: Helper + 'static>(&mut self, name: &str, bindings: Bindings, helper: H) {
+        self.helpers.insert(name, Box::new(helper));
+        self.helpers.set_bindings(name, bindings);
+    }
+
+    /// Adds a helper written in rust which takes and returns JSON values. It's called with the
+    /// parameters, the rendered body and the hash arguments, like helpers added from node.js.
+    pub fn add_json_helper<F>(&mut self, name: &str, helper: F) where F: Fn(Vec<Value>, Option<String>, Map<String, Value>) -> FinchResult<Value> + Send + Sync + 'static {
+        self.register_helper(name, JsonHelper(helper));
+    }
+
+    pub fn remove_helper(&mut self, name: &str) {
+        self.helpers.remove(name);
+    }
+
+    /// Renders a template. Objects and functions in the data which are owned by the host are read through `source`.
+    pub fn compile(&self, name: &str, data: RawValue, source: &mut dyn DataSource) -> FinchResult<String> {
+        let mut res = String::new();
+        self.run(name, data, source, &mut res, None)?;
+        Ok(res)
+    }
+
+    /// Renders a template and passes the output to [DataSource::write] while rendering. A chunk is
+    /// written as soon as static text or a block is done and at least `chunk_size` bytes are
+    /// waiting, so with a `chunk_size` of 0, every piece of the template is written on its own.
+    pub fn stream(&self, name: &str, data: RawValue, source: &mut dyn DataSource, chunk_size: usize) -> FinchResult<()> {
+        let mut res = String::new();
+        self.run(name, data, source, &mut res, Some(chunk_size))?;
+        if !res.is_empty() {
+            source.write(&res)?;
+        }
+        Ok(())
+    }
+
+    fn run(&self, name: &str, data: RawValue, source: &mut dyn DataSource, out: &mut String, flush: Option<usize>) -> FinchResult<()> {
+        let temp = self.templates.get(name).ok_or_else(|| FinchError::TemplateNotExist(name.to_string()))?;
+        temp.program.run_flushing(0..temp.program.instructions.len(), &mut CompilerContext {
+            compiler: self,
+            program: &temp.program,
+            source,
+            cache: Memory::new(),
+            data,
+            original: &temp.source
+        }, out, flush)
+    }
+
+    /// Renders a template with data which lives in rust.
+    pub fn render_raw(&self, name: &str, data: RawValue) -> FinchResult<String> {
+        self.compile(name, data, &mut OwnedSource)
+    }
+
+    /// Renders a template with any serializable data.
+    pub fn render<T: Serialize + ?Sized>(&self, name: &str, data: &T) -> FinchResult<String> {
+        self.render_raw(name, RawValue::from_serialize(data)?)
+    }
+
+    pub fn render_json(&self, name: &str, data: &Value) -> FinchResult<String> {
+        self.render_raw(name, RawValue::from(data))
+    }
+
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::HelperContext;
+
+    // Keeps every chunk a stream writes
+    #[derive(Default)]
+    struct Chunks(Vec<String>);
+
+    impl DataSource for Chunks {
+        fn prop(&mut self, _obj: HostRef, _key: &str) -> FinchResult<RawValue> {
+            Ok(RawValue::Undefined)
+        }
+
+        fn call(&mut self, _func: HostRef, _args: Vec<RawValue>) -> FinchResult<RawValue> {
+            Err(FinchError::Custom(String::from("No functions")))
+        }
+
+        fn write(&mut self, chunk: &str) -> FinchResult<()> {
+            self.0.push(chunk.to_string());
+            Ok(())
+        }
+    }
+
+    // Arrays owned by the source, which logs every read
+    struct Counting {
+        arrays: Vec<Vec<RawValue>>,
+        reads: Vec<String>
+    }
+
+    impl DataSource for Counting {
+        fn prop(&mut self, arr: HostRef, key: &str) -> FinchResult<RawValue> {
+            self.reads.push(format!("prop {} {}", arr.0, key));
+            Ok(RawValue::Undefined)
+        }
+
+        fn len(&mut self, arr: HostRef) -> FinchResult<usize> {
+            self.reads.push(format!("len {}", arr.0));
+            Ok(self.arrays[arr.0].len())
+        }
+
+        fn item(&mut self, arr: HostRef, ind: usize) -> FinchResult<RawValue> {
+            self.reads.push(format!("item {} {}", arr.0, ind));
+            Ok(self.arrays[arr.0].get(ind).cloned().unwrap_or(RawValue::Undefined))
+        }
+
+        fn call(&mut self, _func: HostRef, _args: Vec<RawValue>) -> FinchResult<RawValue> {
+            Err(FinchError::Custom(String::from("No functions")))
+        }
+    }
+
+    // Renders with `xs` set to an array of the numbers, and `nested` to an array holding it
+    fn reads(template: &str, numbers: &[f64]) -> (String, Vec<String>) {
+        let mut compiler = Compiler::new();
+        compiler.add_template("test", template, ParserOptions::default()).unwrap();
+        let mut source = Counting { arrays: vec![numbers.iter().map(|num| RawValue::Number(*num)).collect(), vec![RawValue::HostArray(HostRef(0))]], reads: vec![] };
+        let data = RawValue::from(HashMap::from([(String::from("xs"), RawValue::HostArray(HostRef(0))), (String::from("nested"), RawValue::HostArray(HostRef(1)))]));
+        let res = compiler.compile("test", data, &mut source).unwrap();
+        (res, source.reads)
+    }
+
+    #[test]
+    fn arrays_of_the_source_are_read_lazily() {
+        let numbers = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(reads("{{xs.length}} {{xs.3}}", &numbers), (String::from("5 4"), vec![String::from("len 0"), String::from("item 0 3")]));
+        // Each item is read once, when the loop gets to it
+        let (res, log) = reads("{{#each xs x}}{{#if x > 2}}{{x}}{{/}}{{/}}", &numbers);
+        assert_eq!(res, "345");
+        assert_eq!(log, ["len 0", "item 0 0", "item 0 1", "item 0 2", "item 0 3", "item 0 4"]);
+        let (res, log) = reads("{{#each xs x reverse}}{{x}}{{/}}", &numbers[..3]);
+        assert_eq!(res, "321");
+        assert_eq!(log, ["len 0", "item 0 2", "item 0 1", "item 0 0"]);
+        // Nothing but the length is read when there's nothing to render
+        assert_eq!(reads("{{#each xs x}}{{x}}{{/}}", &[]).1, ["len 0"]);
+        let (res, log) = reads("{{#each nested inner}}{{inner.1}}{{/}}", &numbers);
+        assert_eq!(res, "2");
+        assert_eq!(log, ["len 1", "item 1 0", "item 0 1"]);
+    }
+
+    fn chunks(compiler: &Compiler, name: &str, data: serde_json::Value, chunk_size: usize) -> (FinchResult<()>, Vec<String>) {
+        let mut source = Chunks::default();
+        let res = compiler.stream(name, RawValue::from(&data), &mut source, chunk_size);
+        (res, source.0)
+    }
+
+    #[test]
+    fn streams_flush_between_instructions() {
+        let mut compiler = Compiler::new();
+        compiler.add_template("page", "<ul>{{title}}{{#each xs x}}<li>{{x}}</li>{{/}}{{#with u as u}}<b>{{u.n}}</b>{{/}}</ul>", ParserOptions::default()).unwrap();
+        let data = serde_json::json!({ "title": "T", "xs": [1, 2], "u": { "n": "N" } });
+        let expected = compiler.render_json("page", &data).unwrap();
+        // Inlined loops are flushed item by item, helper blocks once they're done
+        let (res, written) = chunks(&compiler, "page", data.clone(), 0);
+        res.unwrap();
+        assert_eq!(written, ["<ul>", "T", "<li>", "1", "</li>", "<li>", "2", "</li>", "<b>N</b>", "</ul>"]);
+        // Chunks are written once they have at least chunk_size bytes, the rest at the end
+        let (res, written) = chunks(&compiler, "page", data.clone(), 6);
+        res.unwrap();
+        assert_eq!(written, ["<ul>T<li>", "1</li>", "<li>2</li>", "<b>N</b>", "</ul>"]);
+        let (res, written) = chunks(&compiler, "page", data, 1000);
+        res.unwrap();
+        assert_eq!(written, [expected]);
+    }
+
+    #[test]
+    fn streams_keep_what_was_written_before_an_error() {
+        let mut compiler = Compiler::new();
+        compiler.add_template("page", "<p>{{a}}</p>{{b.c.d}}<p>never</p>", ParserOptions::default()).unwrap();
+        let (res, written) = chunks(&compiler, "page", serde_json::json!({ "a": 1 }), 0);
+        assert!(res.is_err());
+        assert_eq!(written, ["<p>", "1", "</p>"]);
+    }
+
+    #[test]
+    fn renders_and_compares_bigints() {
+        let mut compiler = Compiler::new();
+        compiler.add_template("id", "{{id}} {{id == 10n}} {{id == 10}} {{id === 10}} {{id > 9n}} {{-id}}", ParserOptions::default()).unwrap();
+        compiler.add_template("literal", "{{#if 90071992547409930n == 90071992547409931n}}same{{/#else}}different{{/}}", ParserOptions::default()).unwrap();
+        let data = RawValue::from(HashMap::from([(String::from("id"), RawValue::BigInt(10))]));
+        assert_eq!(compiler.render_raw("id", data).unwrap(), "10 true true false true -10");
+        assert_eq!(compiler.render_raw("literal", RawValue::from(HashMap::new())).unwrap(), "different");
+    }
+
+    #[test]
+    fn unicode_names() {
+        let mut compiler = Compiler::new();
+        compiler.register_helper("größe", |ctx: &mut HelperContext| -> FinchResult<String> {
+            Ok(ctx.param(0)?.to_string().to_uppercase())
+        });
+        compiler.add_template("名前", "{{名前.ſ}} {{#größe café /}} {{#each ƒ 項目}}{{項目}}{{/}} {{$_}}", ParserOptions::default()).unwrap();
+        let data = serde_json::json!({ "名前": { "ſ": "Ωμέγα" }, "café": "crème", "ƒ": [1, 2], "$_": "$" });
+        assert_eq!(compiler.render_json("名前", &data).unwrap(), "Ωμέγα CRÈME 12 $");
+    }
+
+    #[test]
+    fn template_helper_with_body_is_an_error() {
+        let mut compiler = Compiler::new();
+        compiler.add_template("user", "{{name}}", ParserOptions::default()).unwrap();
+        compiler.add_template("page", "{{#template \"user\" user}}body{{/}}", ParserOptions::default()).unwrap();
+        let res = compiler.render_json("page", &serde_json::json!({ "user": { "name": "Bob" } }));
+        assert!(matches!(res, Err(FinchError::UnexpectedBody(name)) if name == "template"));
+    }
+
+    #[test]
+    fn bound_variables_shadow_the_data_and_outer_blocks() {
+        let mut compiler = Compiler::new();
+        compiler.add_template("each", "{{#each a x}}{{x.n}}[{{#each x.kids x}}{{x}},{{/}}]{{x.n}};{{/}}{{x}}", ParserOptions::default()).unwrap();
+        compiler.add_template("let", "{{#let a = a.v, b = a}}{{a}}{{b}}{{#let a = 3}}{{a}}{{b}}{{/}}{{a}}{{/}}{{a.v}}", ParserOptions::default()).unwrap();
+        let data = serde_json::json!({ "a": [{ "n": 1, "kids": [5, 6] }, { "n": 2, "kids": [] }], "x": "data" });
+        assert_eq!(compiler.render_json("each", &data).unwrap(), "1[5,6,]1;2[]2;data");
+        assert_eq!(compiler.render_json("let", &serde_json::json!({ "a": { "v": 7 } })).unwrap(), "773777");
+    }
+
+    #[test]
+    fn helpers_can_only_set_declared_variables() {
+        let mut compiler = Compiler::new();
+        compiler.register_helper("bind", |ctx: &mut HelperContext| -> FinchResult<String> {
+            ctx.scope(|ctx| {
+                ctx.set_var("x", RawValue::Number(1.0))?;
+                ctx.render_body()
+            })
+        });
+        compiler.register_binding_helper("declared", Bindings::LastParam(0), |ctx: &mut HelperContext| -> FinchResult<String> {
+            ctx.scope(|ctx| {
+                ctx.set_var("x", RawValue::Number(1.0))?;
+                ctx.render_body()
+            })
+        });
+        compiler.add_template("bind", "{{#bind}}{{x}}{{/}}", ParserOptions::default()).unwrap();
+        compiler.add_template("declared", "{{#declared x}}{{x}}{{/}}", ParserOptions::default()).unwrap();
+        assert!(matches!(compiler.render_json("bind", &serde_json::json!({})), Err(FinchError::UndeclaredVariable(helper, name)) if helper == "bind" && name == "x"));
+        assert_eq!(compiler.render_json("declared", &serde_json::json!({ "x": 2 })).unwrap(), "1");
+    }
+
+    #[test]
+    fn json_helpers_get_the_hash_arguments() {
+        let mut compiler = Compiler::new();
+        compiler.add_json_helper("wrap", |params, body, hash| {
+            let open = hash.get("open").and_then(|open| open.as_str()).unwrap_or("(");
+            let close = hash.get("close").and_then(|close| close.as_str()).unwrap_or(")");
+            Ok(Value::from(format!("{}{}{}{}", open, params[0].as_str().unwrap_or_default(), body.unwrap_or_default(), close)))
+        });
+        compiler.add_template("wrap", "{{#wrap \"a\" open=\"[\" close=end}}body{{/}} {{#wrap \"b\"}}{{/}}", ParserOptions::default()).unwrap();
+        assert_eq!(compiler.render_json("wrap", &serde_json::json!({ "end": "]" })).unwrap(), "[abody] (b)");
+    }
+
+    #[test]
+    fn arithmetic_works_like_javascript() {
+        let mut compiler = Compiler::new();
+        compiler.add_template("numbers", "{{a + b * 2}} {{(a + b) % 4}} {{a - b - 1}} {{a / 0}} {{-a % 2}} {{0.1 + 0.2}} {{n + t}} {{n + u}} {{\" 2 \" * \"3\"}}", ParserOptions::default()).unwrap();
+        compiler.add_template("text", "{{s + a}} {{a + s}} {{xs + 1}} {{xs * 2}} {{o + 1}} {{s - 1}}", ParserOptions::default()).unwrap();
+        compiler.add_template("bigints", "{{7n / 2n}} {{-7n % 2n}} {{2n * 3n - 1n}} {{5n + \"\"}}", ParserOptions::default()).unwrap();
+        compiler.add_template("mixed", "{{a + 1n}}", ParserOptions::default()).unwrap();
+        compiler.add_template("zero", "{{1n % z}}", ParserOptions::default()).unwrap();
+        compiler.add_template("overflow", "{{170141183460469231731687303715884105727n + 1n}}", ParserOptions::default()).unwrap();
+        let data = serde_json::json!({ "a": 7, "b": 2, "s": "x", "xs": [5], "o": {}, "n": null, "t": true });
+        assert_eq!(compiler.render_json("numbers", &data).unwrap(), "11 1 4 Infinity -1 0.30000000000000004 1 NaN 6");
+        assert_eq!(compiler.render_json("text", &data).unwrap(), "x7 7x 51 10 [object Object]1 NaN");
+        assert_eq!(compiler.render_json("bigints", &data).unwrap(), "3 -1 5 5");
+        assert!(matches!(compiler.render_json("mixed", &data), Err(FinchError::MixedBigInt)));
+        let zero = RawValue::from(HashMap::from([(String::from("z"), RawValue::BigInt(0))]));
+        assert!(matches!(compiler.render_raw("zero", zero), Err(FinchError::DivisionByZero)));
+        assert!(matches!(compiler.render_json("overflow", &data), Err(FinchError::BigIntOverflow)));
+    }
+
+}
@@ -0,0 +1,566 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use crate::{compiler::Compiler, helper::{Bindings, Builtin, HelperContext}, value::RawValue, error::{FinchError, FinchResult}, ast::ExpressionKind};
+
+/// The most times `range` and `times` render their body, larger counts are an error rather than a hang
+pub const MAX_ITERATIONS: usize = 1_000_000;
+
+pub fn init(compiler: &mut Compiler) {
+
+    compiler.register_binding_helper("each", Bindings::Param(1), |ctx: &mut HelperContext| -> FinchResult<String> {
+        if let Some(chain) = ctx.chain() {
+            return Err(FinchError::Custom(format!("each has no {} follow up block", chain.name)));
+        }
+        let name = match ctx.param_expression(1) {
+            Some(ExpressionKind::Var(name)) => name,
+            _ => return Err(FinchError::InvalidArg(1))
+        };
+        // The only other parameter is a bare `reverse`
+        let reverse = match ctx.param_expression(2) {
+            Some(ExpressionKind::Var(keyword)) if keyword == "reverse" => true,
+            Some(_) => return Err(FinchError::InvalidArg(2)),
+            None => false
+        };
+        if ctx.len() > 3 {
+            return Err(FinchError::InvalidArg(3));
+        }
+        let items = ctx.param(0)?;
+        let len = items.array_len(ctx.source())?.ok_or(FinchError::InvalidArg(0))?;
+        ctx.scope(|ctx| {
+            let mut res = String::new();
+            if ctx.block().hash.is_empty() {
+                for ind in 0..len {
+                    let item = items.item(if reverse { len - ind - 1 } else { ind }, ctx.source())?;
+                    ctx.set_var(name, item)?;
+                    res += &ctx.render_body()?;
+                }
+            } else {
+                for item in select(ctx, &items, len, name, reverse)? {
+                    ctx.set_var(name, item)?;
+                    res += &ctx.render_body()?;
+                }
+            }
+            Ok(res)
+        })
+    });
+
+    compiler.register_binding_helper("groupBy", Bindings::LastParam(2), |ctx: &mut HelperContext| -> FinchResult<String> {
+        let items = ctx.param(0)?;
+        let len = items.array_len(ctx.source())?.ok_or(FinchError::InvalidArg(0))?;
+        let path = match ctx.param(1)? {
+            RawValue::String(path) => path,
+            _ => return Err(FinchError::InvalidArg(1))
+        };
+        let name = bound_name(ctx, 2)?;
+        if !ctx.has_body() {
+            return Err(FinchError::ExpectedBody(String::from("groupBy")));
+        }
+        // In the order their keys first appear
+        let mut groups: Vec<(RawValue, Vec<RawValue>)> = vec![];
+        for ind in 0..len {
+            let item = items.item(ind, ctx.source())?;
+            let key = key_of(&item, &path, ctx)?;
+            let mut group = None;
+            for (group_ind, (group_key, _)) in groups.iter().enumerate() {
+                if group_key.loose_equals(&key, ctx.source())? {
+                    group = Some(group_ind);
+                    break;
+                }
+            }
+            match group {
+                Some(group_ind) => groups[group_ind].1.push(item),
+                None => groups.push((key, vec![item]))
+            }
+        }
+        ctx.scope(|ctx| {
+            let mut res = String::new();
+            for (key, items) in groups {
+                let group = HashMap::from([(String::from("key"), key), (String::from("items"), RawValue::from(items))]);
+                ctx.set_var(name, RawValue::from(group))?;
+                res += &ctx.render_body()?;
+            }
+            Ok(res)
+        })
+    });
+
+    compiler.register_helper("template", |ctx: &mut HelperContext| -> FinchResult<String> {
+        if ctx.has_body() {
+            return Err(FinchError::UnexpectedBody(String::from("template")));
+        }
+        if let Some(ExpressionKind::String(temp_name)) = ctx.param_expression(0) {
+            let data = ctx.param(1)?;
+            if !matches!(data, RawValue::Object(_) | RawValue::HostObject(_)) {
+                return Err(FinchError::InvalidArg(1));
+            }
+            ctx.render_template(temp_name, data)
+        } else {
+            Err(FinchError::InvalidArg(0))
+        }
+    });
+
+    compiler.register_helper("js", |ctx: &mut HelperContext| -> FinchResult<String> {
+        let body = ctx.body_source().ok_or_else(|| FinchError::ExpectedBody(String::from("js")))?.to_string();
+        let data = ctx.data().clone();
+        let res = ctx.source().eval(&body, &data)?;
+        res.stringify(ctx.source())
+    });
+
+    compiler.register_helper("if", |ctx: &mut HelperContext| -> FinchResult<String> {
+        let exp = ctx.param(0)?;
+        if !ctx.has_body() {
+            return Err(FinchError::ExpectedBody(String::from("if")));
+        }
+        if !exp.is_falsey() {
+            ctx.render_body()
+        } else if let Some(followup) = ctx.chain() {
+            match followup.name.as_str() {
+                "if" => ctx.render_block(followup),
+                "else" => ctx.render_body_of(followup),
+                _ => {
+                    Err(FinchError::Custom(format!("Expected if / else follow up blocks, found {}", followup.name)))
+                }
+            }
+        } else {
+            Ok(String::new())
+        }
+    });
+
+    compiler.register_binding_helper("let", Bindings::HashKeys, |ctx: &mut HelperContext| -> FinchResult<String> {
+        if !ctx.is_empty() {
+            return Err(FinchError::InvalidArg(0));
+        }
+        if !ctx.has_body() {
+            return Err(FinchError::ExpectedBody(String::from("let")));
+        }
+        let hash = &ctx.block().hash;
+        ctx.scope(|ctx| {
+            // Every variable can use the ones before it
+            for (name, exp) in hash {
+                let value = ctx.eval(exp)?;
+                ctx.set_var(name, value)?;
+            }
+            ctx.render_body()
+        })
+    });
+
+    compiler.register_binding_helper("with", Bindings::LastParam(1), |ctx: &mut HelperContext| -> FinchResult<String> {
+        let value = ctx.param(0)?;
+        let name = bound_name(ctx, 1)?;
+        if !ctx.has_body() {
+            return Err(FinchError::ExpectedBody(String::from("with")));
+        }
+        if !value.is_falsey() {
+            ctx.scope(|ctx| {
+                ctx.set_var(name, value)?;
+                ctx.render_body()
+            })
+        } else {
+            render_else(ctx)
+        }
+    });
+
+    compiler.register_helper("unless", |ctx: &mut HelperContext| -> FinchResult<String> {
+        let exp = ctx.param(0)?;
+        if !ctx.has_body() {
+            return Err(FinchError::ExpectedBody(String::from("unless")));
+        }
+        if exp.is_falsey() {
+            ctx.render_body()
+        } else {
+            render_else(ctx)
+        }
+    });
+
+    compiler.register_helper("deepEqual", |ctx: &mut HelperContext| -> FinchResult<String> {
+        let (left, right) = (ctx.param(0)?, ctx.param(1)?);
+        if !ctx.has_body() {
+            return Err(FinchError::ExpectedBody(String::from("deepEqual")));
+        }
+        if left.deep_equals(&right, ctx.source())? {
+            ctx.render_body()
+        } else {
+            render_else(ctx)
+        }
+    });
+
+    compiler.register_helper("and", |ctx: &mut HelperContext| -> FinchResult<String> {
+        all_or_any(ctx, "and", true)
+    });
+
+    compiler.register_helper("or", |ctx: &mut HelperContext| -> FinchResult<String> {
+        all_or_any(ctx, "or", false)
+    });
+
+    // The body of the switch block itself is never rendered, only the bodies of the followups
+    compiler.register_helper("switch", |ctx: &mut HelperContext| -> FinchResult<String> {
+        let value = ctx.param(0)?;
+        let mut default = None;
+        let mut link = ctx.chain();
+        while let Some(block) = link {
+            match block.name.as_str() {
+                "case" => {
+                    if block.params.is_empty() {
+                        return Err(FinchError::InvalidArg(0));
+                    }
+                    for param in &block.params {
+                        let case = ctx.eval(param)?;
+                        if value.strict_equals(&case, ctx.source())? {
+                            return ctx.render_body_of(block);
+                        }
+                    }
+                },
+                "default" if default.is_none() => default = Some(block),
+                "default" => return Err(FinchError::Custom(String::from("A switch can only have one default follow up block"))),
+                _ => return Err(FinchError::Custom(format!("Expected case / default follow up blocks, found {}", block.name)))
+            }
+            link = ctx.chain_of(block);
+        }
+        match default {
+            Some(block) => ctx.render_body_of(block),
+            None => Ok(String::new())
+        }
+    });
+
+    compiler.register_binding_helper("range", Bindings::LastParam(2), |ctx: &mut HelperContext| -> FinchResult<String> {
+        let start = ctx.param(0)?;
+        let end = ctx.param(1)?;
+        let (from, to) = (number(&start, 0)?, number(&end, 1)?);
+        if let RawValue::BigInt(from) = start {
+            return bigint_range(ctx, from, &end);
+        }
+        let step = match ctx.hash("step")? {
+            Some(step) => finite_number(&step).ok_or_else(|| FinchError::InvalidHashArg(String::from("step")))?,
+            None => 1.0
+        };
+        if step <= 0.0 {
+            return Err(FinchError::Custom(String::from("The step of range has to be a positive number")));
+        }
+        let name = bound_name(ctx, 2)?;
+        // The end is included, the small offset keeps it when the division is slightly off. Huge
+        // counts saturate, and are rejected by count_loop
+        let count = ((to - from).abs() / step + 1e-9).floor() as usize;
+        let dir = if to < from { -1.0 } else { 1.0 };
+        count_loop(ctx, "range", name, count.saturating_add(1), |ind| RawValue::Number(from + dir * ind as f64 * step))
+    });
+
+    compiler.register_binding_helper("times", Bindings::LastParam(1), |ctx: &mut HelperContext| -> FinchResult<String> {
+        let times = ctx.param(0)?;
+        let count = number(&times, 0)?.max(0.0).floor() as usize;
+        let name = bound_name(ctx, 1)?;
+        let bigint = matches!(times, RawValue::BigInt(_));
+        count_loop(ctx, "times", name, count, |ind| {
+            if bigint { RawValue::BigInt(ind as i128) } else { RawValue::Number(ind as f64) }
+        })
+    });
+
+    // Templates have the code of these two inlined, see the program module
+    compiler.helpers.mark_builtin("if", Builtin::If);
+    compiler.helpers.mark_builtin("each", Builtin::Each);
+
+}
+
+// Renders the else followup of a block, if it has one
+// Renders the body if `all` of the conditions are truthy, or any of them if `all` isn't set. Like with
+// && and ||, the conditions after the one which decides the result aren't evaluated
+fn all_or_any(ctx: &mut HelperContext, name: &str, all: bool) -> FinchResult<String> {
+    if ctx.is_empty() {
+        return Err(FinchError::InvalidArg(0));
+    }
+    if !ctx.has_body() {
+        return Err(FinchError::ExpectedBody(String::from(name)));
+    }
+    let mut matched = all;
+    for ind in 0..ctx.len() {
+        if ctx.param(ind)?.is_falsey() == all {
+            matched = !all;
+            break;
+        }
+    }
+    if matched {
+        ctx.render_body()
+    } else {
+        render_else(ctx)
+    }
+}
+
+fn render_else(ctx: &mut HelperContext) -> FinchResult<String> {
+    match ctx.chain() {
+        Some(followup) if followup.name == "else" => ctx.render_body_of(followup),
+        Some(followup) => Err(FinchError::Custom(format!("Expected an else follow up block, found {}", followup.name))),
+        None => Ok(String::new())
+    }
+}
+
+// The name of the variable a block binds, the last parameter, either `name` or `as name`. `ind` is where it starts
+fn bound_name<'b>(ctx: &HelperContext<'_, 'b>, ind: usize) -> FinchResult<&'b str> {
+    match (ctx.param_expression(ind), ctx.param_expression(ind + 1)) {
+        (Some(ExpressionKind::Var(name)), None) => Ok(name),
+        (Some(ExpressionKind::Var(keyword)), Some(ExpressionKind::Var(name))) if keyword == "as" && ctx.len() == ind + 2 => Ok(name),
+        _ => Err(FinchError::InvalidArg(ind as i32))
+    }
+}
+
+fn number(val: &RawValue, ind: usize) -> FinchResult<f64> {
+    finite_number(val).ok_or(FinchError::InvalidArg(ind as i32))
+}
+
+fn finite_number(val: &RawValue) -> Option<f64> {
+    match val {
+        RawValue::Number(num) if num.is_finite() => Some(*num),
+        RawValue::BigInt(num) => Some(*num as f64),
+        _ => None
+    }
+}
+
+// A range starting with a BigInt counts in BigInts, so values past 2^53 stay exact. The step has to
+// be a whole number, and a number end is rounded towards the start
+fn bigint_range(ctx: &mut HelperContext, from: i128, end: &RawValue) -> FinchResult<String> {
+    let to = match end {
+        RawValue::BigInt(to) => *to,
+        _ => {
+            let to = number(end, 1)?;
+            if to.floor() as i128 >= from { to.floor() as i128 } else { to.ceil() as i128 }
+        }
+    };
+    let step = match ctx.hash("step")? {
+        Some(RawValue::BigInt(step)) => step,
+        Some(RawValue::Number(step)) if step.is_finite() && step.fract() == 0.0 => step as i128,
+        Some(_) => return Err(FinchError::InvalidHashArg(String::from("step"))),
+        None => 1
+    };
+    if step <= 0 {
+        return Err(FinchError::Custom(String::from("The step of range has to be a positive number")));
+    }
+    let name = bound_name(ctx, 2)?;
+    let count = usize::try_from(from.abs_diff(to) / step as u128).unwrap_or(usize::MAX);
+    // Wrapping arithmetic is exact here, every value lies between the start and the end
+    count_loop(ctx, "range", name, count.saturating_add(1), |ind| {
+        let offset = (ind as u128).wrapping_mul(step as u128) as i128;
+        RawValue::BigInt(if to < from { from.wrapping_sub(offset) } else { from.wrapping_add(offset) })
+    })
+}
+
+// Renders the body `count` times, with the variable set to the value for the iteration
+fn count_loop(ctx: &mut HelperContext, helper: &str, name: &str, count: usize, value: impl Fn(usize) -> RawValue) -> FinchResult<String> {
+    if !ctx.has_body() {
+        return Err(FinchError::ExpectedBody(helper.to_string()));
+    }
+    if count > MAX_ITERATIONS {
+        return Err(FinchError::Custom(format!("{} can't render its body more than {} times", helper, MAX_ITERATIONS)));
+    }
+    ctx.scope(|ctx| {
+        let mut res = String::new();
+        for ind in 0..count {
+            ctx.set_var(name, value(ind))?;
+            res += &ctx.render_body()?;
+        }
+        Ok(res)
+    })
+}
+
+// The items `each` renders when it has hash arguments. They're filtered with `where`, sorted by the
+// `sort` path, reversed with `reverse` (or the bare parameter), and then `offset` items are skipped and
+// at most `limit` are kept
+fn select(ctx: &mut HelperContext, items: &RawValue, len: usize, name: &str, reverse: bool) -> FinchResult<Vec<RawValue>> {
+    let hash = &ctx.block().hash;
+    if let Some((key, _)) = hash.iter().find(|(key, _)| !matches!(key.as_str(), "where" | "sort" | "reverse" | "offset" | "limit")) {
+        return Err(FinchError::Custom(format!("each has no {} argument", key)));
+    }
+    let filter = hash.iter().find(|(key, _)| key == "where").map(|(_, exp)| exp);
+    let mut selected = Vec::with_capacity(len);
+    for ind in 0..len {
+        let item = items.item(ind, ctx.source())?;
+        if let Some(exp) = filter {
+            // The condition can use the variable of the loop
+            ctx.set_var(name, item.clone())?;
+            if ctx.eval(exp)?.is_falsey() {
+                continue;
+            }
+        }
+        selected.push(item);
+    }
+    match ctx.hash("sort")? {
+        Some(RawValue::String(path)) => {
+            let mut keyed = Vec::with_capacity(selected.len());
+            for item in selected {
+                keyed.push((key_of(&item, &path, ctx)?, item));
+            }
+            keyed.sort_by(|(left, _), (right, _)| sort_order(left, right));
+            selected = keyed.into_iter().map(|(_, item)| item).collect();
+        },
+        Some(_) => return Err(FinchError::Custom(String::from("The sort argument of each has to be a string"))),
+        None => {}
+    }
+    if reverse || ctx.hash("reverse")?.is_some_and(|reverse| !reverse.is_falsey()) {
+        selected.reverse();
+    }
+    if let Some(offset) = hash_count(ctx, "offset")? {
+        selected.drain(..offset.min(selected.len()));
+    }
+    if let Some(limit) = hash_count(ctx, "limit")? {
+        selected.truncate(limit);
+    }
+    Ok(selected)
+}
+
+fn hash_count(ctx: &mut HelperContext, key: &str) -> FinchResult<Option<usize>> {
+    match ctx.hash(key)? {
+        Some(RawValue::Number(num)) if num.is_finite() => Ok(Some(num.max(0.0).floor() as usize)),
+        Some(RawValue::BigInt(num)) => Ok(Some(usize::try_from(num.max(0)).unwrap_or(usize::MAX))),
+        Some(_) => Err(FinchError::Custom(format!("The {} argument of each has to be a number", key))),
+        None => Ok(None)
+    }
+}
+
+// Reads a dotted path like `author.name` from an item
+fn key_of(item: &RawValue, path: &str, ctx: &mut HelperContext) -> FinchResult<RawValue> {
+    let mut val = item.clone();
+    for key in path.split('.') {
+        val = val.get(key, ctx.source())?;
+    }
+    Ok(val)
+}
+
+// Numbers come first, then strings, booleans, and everything else. NaN is sorted after the other numbers
+fn sort_order(left: &RawValue, right: &RawValue) -> Ordering {
+    fn rank(val: &RawValue) -> u8 {
+        match val {
+            RawValue::Number(_) | RawValue::BigInt(_) => 0,
+            RawValue::String(_) => 1,
+            RawValue::Boolean(_) => 2,
+            _ => 3
+        }
+    }
+    match (left, right) {
+        (RawValue::BigInt(left), RawValue::BigInt(right)) => left.cmp(right),
+        (RawValue::String(left), RawValue::String(right)) => left.cmp(right),
+        (RawValue::Boolean(left), RawValue::Boolean(right)) => left.cmp(right),
+        _ if rank(left) == 0 && rank(right) == 0 => {
+            let (left, right) = (left.to_number(), right.to_number());
+            left.partial_cmp(&right).unwrap_or_else(|| left.is_nan().cmp(&right.is_nan()))
+        },
+        _ => rank(left).cmp(&rank(right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Compiler, error::FinchError, parser::ParserOptions};
+    use serde_json::{json, Value};
+
+    fn render(template: &str, data: Value) -> Result<String, FinchError> {
+        let mut compiler = Compiler::new();
+        compiler.add_template("test", template, ParserOptions::default())?;
+        compiler.render_json("test", &data)
+    }
+
+    #[test]
+    fn switch() {
+        let template = "{{#switch k}}ignored{{/#case \"a\"}}A{{/#case \"b\" 3}}B{{/#default}}D{{/}}";
+        assert_eq!(render(template, json!({ "k": "a" })).unwrap(), "A");
+        assert_eq!(render(template, json!({ "k": 3 })).unwrap(), "B");
+        // Cases are compared with ===
+        assert_eq!(render(template, json!({ "k": "3" })).unwrap(), "D");
+        assert_eq!(render(template, json!({ "k": "z" })).unwrap(), "D");
+        assert_eq!(render("{{#switch k}}{{/#case 1}}one{{/}}", json!({ "k": 2 })).unwrap(), "");
+        assert!(render("{{#switch k}}{{/#else}}x{{/}}", json!({})).is_err());
+        let twice = render("{{#switch k}}{{/#default}}a{{/#case 1}}b{{/#default}}c{{/}}", json!({ "k": 2 }));
+        assert!(matches!(twice, Err(FinchError::Custom(message)) if message == "A switch can only have one default follow up block"));
+    }
+
+    #[test]
+    fn and_or() {
+        let template = "{{#and a b}}both{{/#else}}not both{{/}} {{#or a b}}one{{/#else}}none{{/}}";
+        assert_eq!(render(template, json!({ "a": 1, "b": "x" })).unwrap(), "both one");
+        assert_eq!(render(template, json!({ "a": 0, "b": "x" })).unwrap(), "not both one");
+        assert_eq!(render(template, json!({ "a": 0, "b": "" })).unwrap(), "not both none");
+        // The conditions after the one which decides aren't evaluated, so they can't fail
+        assert_eq!(render("{{#and a a.b.c}}x{{/#else}}y{{/}}{{#or b a.b.c}}x{{/}}", json!({ "b": true })).unwrap(), "yx");
+        assert!(render("{{#and a.b.c a}}x{{/}}", json!({})).is_err());
+        assert!(matches!(render("{{#or}}x{{/}}", json!({})), Err(FinchError::InvalidArg(0))));
+    }
+
+    #[test]
+    fn let_binds_in_order() {
+        let template = "{{#let a = user.name, b = a == \"Bob\"}}{{a}} {{b}}{{/}} {{a}}";
+        assert_eq!(render(template, json!({ "user": { "name": "Bob" }, "a": "outer" })).unwrap(), "Bob true outer");
+        assert!(matches!(render("{{#let a = 1 /}}", json!({})), Err(FinchError::ExpectedBody(name)) if name == "let"));
+    }
+
+    #[test]
+    fn with() {
+        let template = "{{#with user as u}}{{u.name}}{{/#else}}nobody{{/}}";
+        assert_eq!(render(template, json!({ "user": { "name": "Bob" } })).unwrap(), "Bob");
+        assert_eq!(render(template, json!({ "user": null })).unwrap(), "nobody");
+        assert_eq!(render("{{#with n x}}{{x}}{{/}}", json!({ "n": 0 })).unwrap(), "");
+    }
+
+    #[test]
+    fn unless() {
+        let template = "{{#unless a}}no{{/#else}}yes{{/}}";
+        assert_eq!(render(template, json!({ "a": "" })).unwrap(), "no");
+        assert_eq!(render(template, json!({ "a": 1 })).unwrap(), "yes");
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(render("{{#range 1 4 as i}}{{i}},{{/}}", json!({})).unwrap(), "1,2,3,4,");
+        assert_eq!(render("{{#range 10 1 step=3 i}}{{i}},{{/}}", json!({})).unwrap(), "10,7,4,1,");
+        assert_eq!(render("{{#range 1n 2n i}}{{i == 2n}}{{/}}", json!({})).unwrap(), "falsetrue");
+        assert!(matches!(render("{{#range 1 2 step=\"x\" i}}{{/}}", json!({})), Err(FinchError::InvalidHashArg(key)) if key == "step"));
+        assert!(render("{{#range 1 2 step=0 i}}{{/}}", json!({})).is_err());
+        // BigInt ranges count exactly, past the precision of numbers
+        assert_eq!(render("{{#range 1152921504606846976n 1152921504606846978n i}}{{i}},{{/}}", json!({})).unwrap(), "1152921504606846976,1152921504606846977,1152921504606846978,");
+        assert_eq!(render("{{#range 5n 0.5 step=2n i}}{{i}},{{/}}", json!({})).unwrap(), "5,3,1,");
+        assert!(matches!(render("{{#range 1n 2 step=0.5 i}}{{/}}", json!({})), Err(FinchError::InvalidHashArg(key)) if key == "step"));
+        let too_many = render("{{#range 0 1e12 i}}{{/}}", json!({}));
+        assert!(matches!(too_many, Err(FinchError::Custom(message)) if message == "range can't render its body more than 1000000 times"));
+        let widest = render("{{#range -170141183460469231731687303715884105727n 170141183460469231731687303715884105727n i}}{{/}}", json!({}));
+        assert!(matches!(widest, Err(FinchError::Custom(message)) if message.starts_with("range can't")));
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(render("{{#times n as i}}[{{i}}]{{/}}", json!({ "n": 2.7 })).unwrap(), "[0][1]");
+        assert_eq!(render("{{#times -1 i}}x{{/}}", json!({})).unwrap(), "");
+        assert!(matches!(render("{{#times 2 /}}", json!({})), Err(FinchError::InvalidArg(1))));
+        let too_many = render("{{#times 1e12 i}}{{/}}", json!({}));
+        assert!(matches!(too_many, Err(FinchError::Custom(message)) if message == "times can't render its body more than 1000000 times"));
+        assert_eq!(render("{{#times 1000000 i}}{{/}}", json!({})).unwrap(), "");
+    }
+
+    #[test]
+    fn group_by() {
+        let template = "{{#groupBy items \"kind.name\" as g}}{{g.key}}:{{#each g.items i}}{{i.v}}{{/}};{{/}}";
+        let data = json!({ "items": [{ "kind": { "name": "a" }, "v": 1 }, { "kind": { "name": "b" }, "v": 2 }, { "kind": { "name": "a" }, "v": 3 }] });
+        assert_eq!(render(template, data).unwrap(), "a:13;b:2;");
+    }
+
+    #[test]
+    fn deep_equal() {
+        let template = "{{#deepEqual a b}}same{{/#else}}different{{/}}";
+        assert_eq!(render(template, json!({ "a": { "x": [1, { "y": null }] }, "b": { "x": [1, { "y": null }] } })).unwrap(), "same");
+        assert_eq!(render(template, json!({ "a": [1, 2], "b": [1, "2"] })).unwrap(), "different");
+    }
+
+    #[test]
+    fn each_with_hash_arguments() {
+        let data = json!({ "xs": [{ "n": 3, "on": true }, { "n": 2, "on": false }, { "n": 1, "on": true }, { "n": 4, "on": true }] });
+        assert_eq!(render("{{#each xs x where=(x.on) sort=\"n\"}}{{x.n}}{{/}}", data.clone()).unwrap(), "134");
+        assert_eq!(render("{{#each xs x sort=\"n\" reverse=true offset=1 limit=2}}{{x.n}}{{/}}", data.clone()).unwrap(), "32");
+        assert!(render("{{#each xs x nope=1}}{{/}}", data).is_err());
+    }
+
+    #[test]
+    fn each_reverse_and_unknown_params() {
+        let data = json!({ "xs": [1, 2, 3], "ys": [{ "n": 3 }, { "n": 1 }, { "n": 2 }] });
+        assert_eq!(render("{{#each xs x reverse}}{{x}}{{/}}", data.clone()).unwrap(), "321");
+        assert_eq!(render("{{#each ys y reverse sort=\"n\" limit=2}}{{y.n}}{{/}}", data.clone()).unwrap(), "32");
+        assert!(matches!(render("{{#each xs x backwards}}{{/}}", data.clone()), Err(FinchError::InvalidArg(2))));
+        assert!(matches!(render("{{#each xs x reverse 1}}{{/}}", data.clone()), Err(FinchError::InvalidArg(3))));
+        let followup = render("{{#each xs x}}{{x}}{{/#else}}none{{/}}", data);
+        assert!(matches!(followup, Err(FinchError::Custom(message)) if message == "each has no else follow up block"));
+    }
+
+}
@@ -0,0 +1,122 @@
+
+use std::ops::Range;
+
+#[derive(Debug)]
+pub enum FinchError {
+    ExpectedFound(char, char),
+    Expected(char),
+    Unexpected(char),
+    MissingPropName,
+    MissingBlockName,
+    InvalidNumber,
+    PropNotExist(String),
+    TemplateNotExist(String),
+    InvalidArg(i32),
+    /// A hash argument with the wrong type, by its key
+    InvalidHashArg(String),
+    ExpectedObject,
+    NotCallable,
+    ErrInFunction,
+    External(String),
+    HelperNotFound(String),
+    /// `>`, `<`, `>=` or `<=` on values which aren't both numbers or both strings
+    NotNumbers,
+    /// An arithmetic operator on a BigInt and a value which isn't one
+    MixedBigInt,
+    /// `/` or `%` with a BigInt 0 on the right
+    DivisionByZero,
+    /// Arithmetic on BigInts whose result doesn't fit in an i128
+    BigIntOverflow,
+    ExpectedBody(String),
+    UnexpectedBody(String),
+    UnclosedBlock(String),
+    /// A helper set a variable which it didn't declare, the name of the helper and the variable
+    UndeclaredVariable(String, String),
+    InvalidBundle(String),
+    /// A condition which doesn't depend on the data, it's always true or always false
+    ConstantCondition(bool),
+    Custom(String),
+    None
+}
+
+pub type FinchResult<T> = Result<T, FinchError>;
+
+impl std::fmt::Display for FinchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ExpectedFound(expected, found) => write!(f, "Expected character '{}', but found '{}'", expected, found),
+            Self::Expected(expected) => write!(f, "Expected character '{}'", expected),
+            Self::None => write!(f, "An unknown error occured"),
+            Self::Unexpected(unexpected) => write!(f, "Unexpected character '{}'", unexpected),
+            Self::InvalidNumber => write!(f, "Invalid number literal, or a BigInt literal which doesn't fit in a 128-bit signed integer (i128)"),
+            Self::MissingPropName => write!(f, "Expected property name after dot (.)"),
+            Self::MissingBlockName => write!(f, "Expected block name after hash (#)"),
+            Self::PropNotExist(prop) => write!(f, "Property '{}' does not exist", prop),
+            Self::InvalidArg(n) => write!(f, "Argument {} is invalid", n),
+            Self::InvalidHashArg(key) => write!(f, "Hash argument {} is invalid", key),
+            Self::TemplateNotExist(temp_name) => write!(f, "The template {} doesn't exist", temp_name),
+            Self::ExpectedObject => write!(f, "Expected type object for dot notation."),
+            Self::NotCallable => write!(f, "Property is not callable."),
+            Self::ErrInFunction => write!(f, "An error occured in a JS function"),
+            Self::External(text) => write!(f, "{}", text),
+            Self::HelperNotFound(helper_name) => write!(f, "Couldn't find helper \"{}\"", helper_name),
+            Self::NotNumbers => write!(f, "Cannot use >, <, >=, <= on values which aren't both numbers or both strings"),
+            Self::MixedBigInt => write!(f, "Cannot mix BigInt and other types, use explicit conversions"),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::BigIntOverflow => write!(f, "The result doesn't fit in a 128-bit signed integer (i128)"),
+            Self::ExpectedBody(temp) => write!(f, "Expected body for {} helper", temp),
+            Self::UnexpectedBody(temp) => write!(f, "The {} helper cannot have a body", temp),
+            Self::UnclosedBlock(temp) => write!(f, "Block {} is never closed with {{{{/}}}}", temp),
+            Self::UndeclaredVariable(helper, name) => write!(f, "The {} helper sets the variable {}, which it didn't declare when it was registered", helper, name),
+            Self::InvalidBundle(reason) => write!(f, "Invalid template bundle: {}", reason),
+            Self::ConstantCondition(val) => write!(f, "This condition is always {}", val),
+            Self::Custom(st) => write!(f, "{}", st)
+        }
+    }
+}
+
+impl std::error::Error for FinchError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The template can't be rendered
+    Error,
+    /// The template works, but probably not like it was meant to
+    Warning
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning")
+        }
+    }
+}
+
+/// An error tied to the part of the template which caused it.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub error: FinchError,
+    pub severity: Severity,
+    // Byte offsets in the template source
+    pub span: Range<usize>
+}
+
+impl Diagnostic {
+
+    // Line and column (in characters) where the diagnostic starts, both 1-based
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.span.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|ind| ind + 1).unwrap_or(0);
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}..{})", self.error, self.span.start, self.span.end)
+    }
+}
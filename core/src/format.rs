@@ -0,0 +1,260 @@
+//! Re-emits templates in a canonical form.
+//!
+//! Tags are printed without padding (`{{ x }}` becomes `{{x}}`), helper arguments are separated
+//! by a single space, and expressions only keep the parentheses they need. The text between tags
+//! is left alone, so `parse(format(x)) == parse(x)`, unless [FormatOptions::indent] is set.
+
+use crate::ast::*;
+use crate::error::FinchResult;
+use crate::parser::{Parser, ParserOptions};
+
+#[derive(Default, Clone, Copy)]
+pub struct FormatOptions {
+    /// The options the template is parsed with
+    pub parser: ParserOptions,
+    /// Re-indent the bodies of multi-line blocks by this many spaces, relative to the line
+    /// of the opening tag. This changes the whitespace in the template's text.
+    pub indent: Option<usize>
+}
+
+pub fn format(source: &str, options: FormatOptions) -> FinchResult<String> {
+    let ast = Parser::parse(source, options.parser)?;
+    Ok(Formatter::new(options).format(&ast))
+}
+
+pub struct Formatter {
+    options: FormatOptions
+}
+
+impl Formatter {
+
+    pub fn new(options: FormatOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn format(&self, text: &SubText) -> String {
+        let mut res = String::new();
+        self.format_subtext(text, &mut res);
+        res
+    }
+
+    fn format_subtext(&self, text: &SubText, res: &mut String) {
+        for template in &text.templates {
+            match &template.kind {
+                TemplateKind::Text(text) => res.push_str(text),
+                TemplateKind::Expression(exp) => {
+                    res.push_str("{{");
+                    res.push_str(&self.format_expression(exp));
+                    res.push_str("}}");
+                },
+                TemplateKind::Block(block) => {
+                    res.push_str("{{#");
+                    self.format_block(block, res);
+                },
+                TemplateKind::Error => {}
+            }
+        }
+    }
+
+    // Everything after the {{# or {{/#
+    fn format_block(&self, block: &FnBlock, res: &mut String) {
+        res.push_str(&block.name);
+        for (ind, param) in block.params.iter().enumerate() {
+            let param = self.format_expression(param);
+            // A space before a ( would turn the previous argument into a function call
+            res.push_str(if ind != 0 && param.starts_with('(') { ", " } else { " " });
+            res.push_str(&param);
+        }
+        for (key, value) in &block.hash {
+            res.push(' ');
+            res.push_str(key);
+            res.push('=');
+            res.push_str(&self.format_expression(value));
+        }
+        let body = if let Some(body) = &block.block { body } else {
+            res.push_str(" /}}");
+            return;
+        };
+        res.push_str("}}");
+        let mut body_str = String::new();
+        self.format_subtext(body, &mut body_str);
+        if let Some(width) = self.options.indent {
+            let tag_indent = line_indent(res);
+            body_str = reindent(&body_str, tag_indent + width, tag_indent);
+        }
+        res.push_str(&body_str);
+        if let Some(chain) = &block.chain {
+            res.push_str("{{/#");
+            self.format_block(chain, res);
+        } else {
+            res.push_str("{{/}}");
+        }
+    }
+
+    pub fn format_expression(&self, exp: &ExpressionKind) -> String {
+        self.format_expression_prec(exp, 0)
+    }
+
+    // Wraps the expression in parentheses if it binds weaker than `min_prec`
+    fn format_expression_prec(&self, exp: &ExpressionKind, min_prec: i8) -> String {
+        let prec = precedence(exp);
+        let res = match exp {
+            ExpressionKind::Var(name) => name.clone(),
+            ExpressionKind::VarDot(path) => path.join("."),
+            ExpressionKind::Number(num) => self.format_number(*num),
+            ExpressionKind::BigInt(num) => format!("{}n", num),
+            ExpressionKind::String(str) => format!("\"{}\"", str.replace('\\', "\\\\").replace('"', "\\\"")),
+            ExpressionKind::Bool(bol) => bol.to_string(),
+            ExpressionKind::Undefined => String::from("undefined"),
+            ExpressionKind::Null => String::from("null"),
+            ExpressionKind::Binary(op) => {
+                let (left, right) = op.operands();
+                let op_str = match &**op {
+                    BinaryOps::Compare(_, _) => "==",
+                    BinaryOps::Not(_, _) => "!=",
+                    BinaryOps::StrictCompare(_, _) => "===",
+                    BinaryOps::StrictNot(_, _) => "!==",
+                    BinaryOps::Gt(_, _) => ">",
+                    BinaryOps::Lt(_, _) => "<",
+                    BinaryOps::Gte(_, _) => ">=",
+                    BinaryOps::Lte(_, _) => "<=",
+                    BinaryOps::And(_, _) => "&&",
+                    BinaryOps::Or(_, _) => "||",
+                    BinaryOps::Add(_, _) => "+",
+                    BinaryOps::Sub(_, _) => "-",
+                    BinaryOps::Mul(_, _) => "*",
+                    BinaryOps::Div(_, _) => "/",
+                    BinaryOps::Rem(_, _) => "%"
+                };
+                // Left-associative, so only the right side needs parentheses on equal precedence
+                format!("{} {} {}", self.format_expression_prec(left, prec), op_str, self.format_expression_prec(right, prec + 1))
+            },
+            ExpressionKind::Unary(op) => {
+                let op_str = match &**op {
+                    UnaryOps::Not(_) => "!",
+                    UnaryOps::Neg(_) => "-"
+                };
+                format!("{}{}", op_str, self.format_expression_prec(op.operand(), prec))
+            },
+            ExpressionKind::Call { var, params } => {
+                format!("{}({})", self.format_expression_prec(var, prec), params.iter().map(|param| self.format_expression(param)).collect::<Vec<String>>().join(", "))
+            }
+        };
+        if prec < min_prec {
+            format!("({})", res)
+        } else {
+            res
+        }
+    }
+
+    fn format_number(&self, num: f64) -> String {
+        if num.is_nan() {
+            String::from("NaN")
+        } else if num.is_infinite() {
+            String::from(if num > 0.0 { "Infinity" } else { "-Infinity" })
+        } else if num.abs() >= 1e21 {
+            format!("{:e}", num)
+        } else if num.fract() == 0.0 && self.options.parser.bigint_integers {
+            // Otherwise the number would be read back as a BigInt
+            format!("{:.1}", num)
+        } else {
+            num.to_string()
+        }
+    }
+
+}
+
+fn precedence(exp: &ExpressionKind) -> i8 {
+    match exp {
+        ExpressionKind::Binary(op) => match &**op {
+            BinaryOps::Or(_, _) => 1,
+            BinaryOps::And(_, _) => 2,
+            BinaryOps::Compare(_, _) | BinaryOps::Not(_, _) | BinaryOps::StrictCompare(_, _) | BinaryOps::StrictNot(_, _) => 3,
+            BinaryOps::Gt(_, _) | BinaryOps::Lt(_, _) | BinaryOps::Gte(_, _) | BinaryOps::Lte(_, _) => 4,
+            BinaryOps::Add(_, _) | BinaryOps::Sub(_, _) => 5,
+            BinaryOps::Mul(_, _) | BinaryOps::Div(_, _) | BinaryOps::Rem(_, _) => 6
+        },
+        ExpressionKind::Unary(_) => 7,
+        // Negative literals are printed with a leading -
+        ExpressionKind::Number(num) if num.is_sign_negative() && !num.is_nan() => 7,
+        ExpressionKind::BigInt(num) if *num < 0 => 7,
+        _ => 8
+    }
+}
+
+// The leading whitespace of the last line
+fn line_indent(text: &str) -> usize {
+    let line = &text[text.rfind('\n').map(|ind| ind + 1).unwrap_or(0)..];
+    line.chars().take_while(|ch| *ch == ' ' || *ch == '\t').count()
+}
+
+// Shifts all lines of a block body after the first one, so the least indented line is indented
+// by `indent`. If the last line only holds the closing tag, it's indented by `close_indent`.
+fn reindent(body: &str, indent: usize, close_indent: usize) -> String {
+    let lines: Vec<&str> = body.split('\n').collect();
+    if lines.len() < 2 {
+        return body.to_string();
+    }
+    let is_blank = |line: &str| line.chars().all(|ch| ch == ' ' || ch == '\t');
+    let leading = |line: &str| line.len() - line.trim_start_matches(&[' ', '\t'][..]).len();
+    let min_indent = lines[1..].iter().filter(|line| !is_blank(line)).map(|line| leading(line)).min().unwrap_or(0);
+    let mut res = String::from(lines[0]);
+    for (ind, line) in lines.iter().enumerate().skip(1) {
+        res.push('\n');
+        if !is_blank(line) {
+            res.push_str(&" ".repeat(indent));
+            res.push_str(&line[min_indent..]);
+        } else if ind == lines.len() - 1 {
+            res.push_str(&" ".repeat(close_indent));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(source: &str) {
+        round_trip_with(source, ParserOptions::default());
+    }
+
+    fn round_trip_with(source: &str, parser: ParserOptions) {
+        let options = FormatOptions { parser, ..FormatOptions::default() };
+        let formatted = format(source, options).unwrap();
+        assert_eq!(Parser::parse(&formatted, parser).unwrap(), Parser::parse(source, parser).unwrap(), "{}", formatted);
+        // Formatting is idempotent
+        assert_eq!(format(&formatted, options).unwrap(), formatted);
+    }
+
+    #[test]
+    fn formats_tags_canonically() {
+        let source = "{{ user.name }} {{#if  a==1 }}one{{/#if (a > 2) && !b}}two{{/#else}}{{/}} {{ NaN }}";
+        assert_eq!(format(source, FormatOptions::default()).unwrap(), "{{user.name}} {{#if a == 1}}one{{/#if a > 2 && !b}}two{{/#else}}{{/}} {{NaN}}");
+    }
+
+    #[test]
+    fn parses_formatted_templates_the_same() {
+        round_trip("<p>{{ name }}</p>\n{{#each items item where=(item.on || x) sort=\"n\"}}\n  {{item.n}}\n{{/}}");
+        round_trip("{{a || b && c}} {{(a || b) && c}} {{!(a == b)}} {{-(x)}} {{-3}} {{-5n}} {{f(1, g(2))(3)}}");
+        round_trip("{{1_000.5}} {{0x1F}} {{1e21}} {{.5}} {{Infinity}} {{\"say \\\"hi\\\"\"}} {{undefined}} {{null}}");
+        round_trip("{{#let a = 1, b = a == 1}}{{b}}{{/}}{{#shout \"x\" (y)/}}{{#switch k}}{{/#case 1 2}}A{{/#default}}B{{/}}");
+        round_trip("{{a + b * c}} {{(a + b) * c}} {{a - (b - c)}} {{a - -b}} {{-a % 2}} {{a / b}} {{a > b + 1}}{{#range 1 -5 i}}{{/}}{{#range a - 1 -b i}}{{/}}{{#h a / b /}}");
+        round_trip("{{#with user as u}}{{u.名前}}{{/#else}}none{{/}}{{#range 1 10 step=2 as i}}{{i}}{{/}}{{#times n i}}*{{/}}");
+    }
+
+    #[test]
+    fn round_trips_the_parser_corpus() {
+        for source in crate::parser::tests::CORPUS {
+            round_trip(source);
+            round_trip_with(source, ParserOptions { bigint_integers: true });
+        }
+    }
+
+    #[test]
+    fn reindents_block_bodies() {
+        let options = FormatOptions { indent: Some(2), ..FormatOptions::default() };
+        assert_eq!(format("{{#if a}}\n        x\n{{/}}", options).unwrap(), "{{#if a}}\n  x\n{{/}}");
+    }
+
+}
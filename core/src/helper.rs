@@ -0,0 +1,307 @@
+//! Block helpers, `{{#name ...}}`. Everything a helper can do goes through [HelperContext],
+//! so helpers don't depend on how the renderer works internally.
+
+use std::collections::HashMap;
+use serde_json::{Map, Value};
+use crate::ast::ExpressionKind;
+use crate::compiler::{Compiler, CompilerContext};
+use crate::error::{FinchError, FinchResult};
+use crate::program::{Block, Expression};
+use crate::value::{DataSource, RawValue};
+
+/// A block helper. Closures taking a [HelperContext] are helpers too:
+///
+/// ```
+/// # use finch_core::{Compiler, helper::HelperContext, error::FinchResult};
+/// let prefix = String::from("> ");
+/// let mut compiler = Compiler::new();
+/// compiler.register_helper("quote", move |ctx: &mut HelperContext| -> FinchResult<String> {
+///     Ok(format!("{}{}", prefix, ctx.render_body()?))
+/// });
+/// ```
+pub trait Helper: Send + Sync {
+    fn call(&self, ctx: &mut HelperContext) -> FinchResult<String>;
+}
+
+impl<F> Helper for F where F: Fn(&mut HelperContext) -> FinchResult<String> + Send + Sync {
+    fn call(&self, ctx: &mut HelperContext) -> FinchResult<String> {
+        self(ctx)
+    }
+}
+
+/// The helpers which have their code inlined into templates. The inlined code only runs
+/// while the helper with the same name is the built-in one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    If,
+    Each
+}
+
+/// The variables a helper binds for its body, with [HelperContext::set_var]. Templates are lowered
+/// knowing them, so variables are read from where they're kept instead of being looked up by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bindings {
+    /// The parameter at the index names the variable, like `item` in `{{#each items item}}`. The hash
+    /// arguments can use it, it's set before they're evaluated
+    Param(usize),
+    /// The parameters from the index name the variable, either `name` or `as name`, and are the last ones.
+    /// The hash arguments are evaluated before it's set
+    LastParam(usize),
+    /// Every hash key is a variable, which the hash arguments after it can use, like in `{{#let a = 1, b = a}}`
+    HashKeys
+}
+
+#[derive(Default)]
+struct HelperSlot {
+    helper: Option<Box<dyn Helper>>,
+    builtin: Option<Builtin>,
+    bindings: Option<Bindings>
+}
+
+/// The helpers of a [Compiler]. Every helper name gets a slot the first time it's used,
+/// lowered templates refer to helpers by their slot, so they don't have to look them up by name.
+#[derive(Default)]
+pub struct HelperTable {
+    names: HashMap<String, usize>,
+    slots: Vec<HelperSlot>
+}
+
+impl HelperTable {
+
+    /// The slot of a helper, it's allocated if the name doesn't have one yet.
+    pub fn slot(&mut self, name: &str) -> usize {
+        if let Some(slot) = self.names.get(name) {
+            return *slot;
+        }
+        self.slots.push(HelperSlot::default());
+        self.names.insert(name.to_string(), self.slots.len() - 1);
+        self.slots.len() - 1
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&dyn Helper> {
+        self.slots.get(slot).and_then(|slot| slot.helper.as_deref())
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&dyn Helper> {
+        self.names.get(name).and_then(|slot| self.get(*slot))
+    }
+
+    pub fn insert(&mut self, name: &str, helper: Box<dyn Helper>) {
+        let slot = self.slot(name);
+        self.slots[slot] = HelperSlot { helper: Some(helper), builtin: None, bindings: None };
+    }
+
+    /// Declares the variables a helper binds, they're forgotten when the helper is replaced.
+    pub fn set_bindings(&mut self, name: &str, bindings: Bindings) {
+        let slot = self.slot(name);
+        self.slots[slot].bindings = Some(bindings);
+    }
+
+    pub fn bindings(&self, slot: usize) -> Option<Bindings> {
+        self.slots.get(slot).and_then(|slot| slot.bindings)
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        if let Some(slot) = self.names.get(name) {
+            self.slots[*slot] = HelperSlot::default();
+        }
+    }
+
+    /// If the slot holds the built-in helper.
+    pub fn is_builtin(&self, slot: usize, builtin: Builtin) -> bool {
+        self.slots.get(slot).is_some_and(|slot| slot.builtin == Some(builtin))
+    }
+
+    pub(crate) fn mark_builtin(&mut self, name: &str, builtin: Builtin) {
+        let slot = self.slot(name);
+        self.slots[slot].builtin = Some(builtin);
+    }
+
+}
+
+/// The block a helper was called with, and the state of the render it's part of.
+pub struct HelperContext<'a, 'b> {
+    block: &'b Block,
+    ctx: &'a mut CompilerContext<'b>
+}
+
+impl<'a, 'b> HelperContext<'a, 'b> {
+
+    pub fn new(block: &'b Block, ctx: &'a mut CompilerContext<'b>) -> Self {
+        Self { block, ctx }
+    }
+
+    pub fn name(&self) -> &'b str {
+        &self.block.name
+    }
+
+    pub fn block(&self) -> &'b Block {
+        self.block
+    }
+
+    /// The number of positional parameters.
+    pub fn len(&self) -> usize {
+        self.block.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.block.params.is_empty()
+    }
+
+    /// The expression of a parameter, for helpers which don't want it evaluated,
+    /// like the name of the variable in `{{#each items item}}`.
+    pub fn param_expression(&self, ind: usize) -> Option<&'b ExpressionKind> {
+        self.block.params.get(ind).map(|param| &param.ast)
+    }
+
+    /// Evaluates a parameter, fails with [FinchError::InvalidArg] if it's missing.
+    pub fn param(&mut self, ind: usize) -> FinchResult<RawValue> {
+        match self.block.params.get(ind) {
+            Some(param) => param.eval(self.ctx),
+            None => Err(FinchError::InvalidArg(ind as i32))
+        }
+    }
+
+    /// Evaluates an expression in the scope of the block, like a parameter.
+    pub fn eval(&mut self, exp: &Expression) -> FinchResult<RawValue> {
+        exp.eval(self.ctx)
+    }
+
+    /// Evaluates all positional parameters.
+    pub fn params(&mut self) -> FinchResult<Vec<RawValue>> {
+        let mut res = Vec::with_capacity(self.block.params.len());
+        for param in &self.block.params {
+            res.push(param.eval(self.ctx)?);
+        }
+        Ok(res)
+    }
+
+    /// Evaluates the hash argument with the given key, `key=value`.
+    pub fn hash(&mut self, key: &str) -> FinchResult<Option<RawValue>> {
+        match self.block.hash.iter().find(|(name, _)| name == key) {
+            Some((_, value)) => Ok(Some(value.eval(self.ctx)?)),
+            None => Ok(None)
+        }
+    }
+
+    /// Evaluates all hash arguments.
+    pub fn hash_map(&mut self) -> FinchResult<HashMap<String, RawValue>> {
+        let mut res = HashMap::with_capacity(self.block.hash.len());
+        for (key, value) in &self.block.hash {
+            res.insert(key.clone(), value.eval(self.ctx)?);
+        }
+        Ok(res)
+    }
+
+    pub fn has_body(&self) -> bool {
+        self.block.body_pos.is_some()
+    }
+
+    /// Renders the body of the block, fails with [FinchError::ExpectedBody] if it doesn't have one.
+    pub fn render_body(&mut self) -> FinchResult<String> {
+        self.render_body_of(self.block)
+    }
+
+    /// Renders the body of another block of the template, usually the [chain](Self::chain),
+    /// without calling its helper.
+    pub fn render_body_of(&mut self, block: &'b Block) -> FinchResult<String> {
+        match &block.body {
+            Some(body) => {
+                let mut res = String::new();
+                self.ctx.program.run(body.clone(), self.ctx, &mut res)?;
+                Ok(res)
+            },
+            None => Err(FinchError::ExpectedBody(block.name.clone()))
+        }
+    }
+
+    /// The body of the block as it was written in the template.
+    pub fn body_source(&self) -> Option<&str> {
+        self.block.body_pos.as_ref().map(|pos| &self.ctx.original[pos.clone()])
+    }
+
+    /// The followup block, `{{/#name ...}}`.
+    pub fn chain(&self) -> Option<&'b Block> {
+        self.chain_of(self.block)
+    }
+
+    /// The followup block of another block of the template, to walk the whole chain.
+    pub fn chain_of(&self, block: &'b Block) -> Option<&'b Block> {
+        block.chain.map(|chain| &self.ctx.program.blocks[chain])
+    }
+
+    /// Renders a block with the helper it's named after, usually the [chain](Self::chain).
+    pub fn render_block(&mut self, block: &'b Block) -> FinchResult<String> {
+        block.call(self.ctx)
+    }
+
+    /// Runs `func` in a new scope, variables set with [set_var](Self::set_var) inside of it
+    /// are gone after it returns.
+    pub fn scope<T>(&mut self, func: impl FnOnce(&mut Self) -> FinchResult<T>) -> FinchResult<T> {
+        self.ctx.cache.extend();
+        let res = func(self);
+        self.ctx.cache.destroy();
+        res
+    }
+
+    /// Sets a variable in the current scope. Only the variables the helper declared with [Bindings] can
+    /// be set, the template was lowered with them.
+    pub fn set_var(&mut self, name: &str, value: RawValue) -> FinchResult<()> {
+        match self.block.bindings.iter().find(|(var, _)| var == name) {
+            Some((_, slot)) => {
+                self.ctx.cache.set(*slot, value);
+                Ok(())
+            },
+            None => Err(FinchError::UndeclaredVariable(self.block.name.clone(), name.to_string()))
+        }
+    }
+
+    /// The data the template is rendered with.
+    pub fn data(&self) -> &RawValue {
+        &self.ctx.data
+    }
+
+    pub fn compiler(&self) -> &'b Compiler {
+        self.ctx.compiler
+    }
+
+    pub fn source(&mut self) -> &mut dyn DataSource {
+        self.ctx.source
+    }
+
+    /// Renders another template with the given data, in the same render.
+    pub fn render_template(&mut self, name: &str, data: RawValue) -> FinchResult<String> {
+        self.ctx.compiler.compile(name, data, self.ctx.source)
+    }
+
+}
+
+/// A helper implemented by the host, it's called with [DataSource::call_helper].
+pub struct HostHelper;
+
+impl Helper for HostHelper {
+    fn call(&self, ctx: &mut HelperContext) -> FinchResult<String> {
+        let params = ctx.params()?;
+        let hash = ctx.hash_map()?;
+        let body = if ctx.has_body() { Some(ctx.render_body()?) } else { None };
+        let name = ctx.name();
+        ctx.source().call_helper(name, params, hash, body)
+    }
+}
+
+pub(crate) struct JsonHelper<F>(pub F);
+
+impl<F> Helper for JsonHelper<F> where F: Fn(Vec<Value>, Option<String>, Map<String, Value>) -> FinchResult<Value> + Send + Sync {
+    fn call(&self, ctx: &mut HelperContext) -> FinchResult<String> {
+        let mut params = vec![];
+        for param in ctx.params()? {
+            params.push(param.copy(ctx.source())?.to_json()?);
+        }
+        let mut hash = Map::new();
+        for (key, value) in ctx.hash_map()? {
+            hash.insert(key, value.copy(ctx.source())?.to_json()?);
+        }
+        let body = if ctx.has_body() { Some(ctx.render_body()?) } else { None };
+        Ok(RawValue::from(&(self.0)(params, body, hash)?).into_string())
+    }
+}
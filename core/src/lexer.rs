@@ -0,0 +1,478 @@
+use std::ops::Range;
+use unicode_xid::UnicodeXID;
+use crate::error::*;
+use crate::parser::ParserOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Eq,
+    NotEq,
+    StrictEq,
+    StrictNotEq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    And,
+    Or,
+    Not,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    // Static text outside of tags, the contents are in the token's span
+    Text,
+    // {{
+    OpenTag,
+    // }}
+    CloseTag,
+    Hash,
+    Slash,
+    Ident(String),
+    String(String),
+    Number(f64),
+    BigInt(i128),
+    Operator(Operator),
+    OpenParen,
+    CloseParen,
+    Comma,
+    Dot,
+    // A single =, used by hash arguments
+    Assign,
+    // Never produced by the lexer, the recovering parser puts it in place of tokens the lexer failed to read
+    Invalid
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>
+}
+
+/// Splits a template into tokens. Text outside of `{{ }}` becomes a single [TokenKind::Text] token,
+/// everything inside of tags is split into expression tokens, skipping any unicode whitespace.
+///
+/// The lexer keeps going after an error, so every error can be collected in one pass.
+/// Unclosed tags end where the next `{{` starts.
+pub struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+    options: ParserOptions,
+    in_tag: bool,
+    // Whether the previous token can be followed by a `.` member access
+    after_operand: bool,
+    after_dot: bool
+}
+
+impl<'a> Lexer<'a> {
+
+    pub fn new(source: &'a str, options: ParserOptions) -> Self {
+        Self {
+            source,
+            pos: 0,
+            options,
+            in_tag: false,
+            after_operand: false,
+            after_dot: false
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn is_next(&self, ch: char) -> bool {
+        self.peek() == Some(ch)
+    }
+
+    fn token(&self, kind: TokenKind, start: usize) -> Token {
+        Token { kind, span: start..self.pos }
+    }
+
+    fn next_text(&mut self) -> Option<Token> {
+        let start = self.pos;
+        if start >= self.source.len() {
+            return None;
+        }
+        match self.source[start..].find("{{") {
+            Some(0) => {
+                self.pos += 2;
+                self.in_tag = true;
+                self.after_operand = false;
+                self.after_dot = false;
+                Some(self.token(TokenKind::OpenTag, start))
+            },
+            Some(ind) => {
+                self.pos += ind;
+                Some(self.token(TokenKind::Text, start))
+            },
+            None => {
+                self.pos = self.source.len();
+                Some(self.token(TokenKind::Text, start))
+            }
+        }
+    }
+
+    fn next_in_tag(&mut self, start: usize) -> Option<FinchResult<Token>> {
+        let ch = self.bump()?;
+        let after_dot = std::mem::replace(&mut self.after_dot, false);
+        let after_operand = std::mem::replace(&mut self.after_operand, false);
+        let kind = match ch {
+            // Members after a dot can also start with a digit (`arr.0`)
+            ch if after_dot && is_ident_continue(ch) => {
+                self.after_operand = true;
+                TokenKind::Ident(self.read_ident(start))
+            },
+            ch if is_ident_start(ch) => {
+                self.after_operand = true;
+                TokenKind::Ident(self.read_ident(start))
+            },
+            '0'..='9' => {
+                self.after_operand = true;
+                return Some(self.read_number(start));
+            },
+            '.' if !after_operand && matches!(self.peek(), Some('0'..='9')) => {
+                self.after_operand = true;
+                return Some(self.read_number(start));
+            },
+            '.' => {
+                self.after_dot = true;
+                TokenKind::Dot
+            },
+            '"' => {
+                self.after_operand = true;
+                return Some(self.read_string(start));
+            },
+            // An unclosed tag, the new one starts here
+            '{' if self.is_next('{') => {
+                self.bump();
+                TokenKind::OpenTag
+            },
+            '}' => {
+                match self.peek() {
+                    Some('}') => {},
+                    Some(found) => return Some(Err(FinchError::ExpectedFound('}', found))),
+                    None => return Some(Err(FinchError::Expected('}')))
+                }
+                self.bump();
+                self.in_tag = false;
+                TokenKind::CloseTag
+            },
+            '#' => TokenKind::Hash,
+            // After an operand `/` divides, unless it ends a block without a body (`{{#helper a /}}`)
+            '/' if after_operand && !self.source[self.pos..].trim_start().starts_with('}') => TokenKind::Operator(Operator::Slash),
+            '/' => TokenKind::Slash,
+            ',' => TokenKind::Comma,
+            '(' => TokenKind::OpenParen,
+            ')' => {
+                self.after_operand = true;
+                TokenKind::CloseParen
+            },
+            '=' => {
+                if self.is_next('=') {
+                    self.bump();
+                    TokenKind::Operator(self.with_optional_eq(Operator::Eq, Operator::StrictEq))
+                } else { TokenKind::Assign }
+            },
+            '&' => return Some(self.expect_second('&', Operator::And, start)),
+            '|' => return Some(self.expect_second('|', Operator::Or, start)),
+            '!' => TokenKind::Operator(match self.with_optional_eq(Operator::Not, Operator::NotEq) {
+                Operator::NotEq => self.with_optional_eq(Operator::NotEq, Operator::StrictNotEq),
+                op => op
+            }),
+            '>' => TokenKind::Operator(self.with_optional_eq(Operator::Gt, Operator::Gte)),
+            '<' => TokenKind::Operator(self.with_optional_eq(Operator::Lt, Operator::Lte)),
+            '+' => TokenKind::Operator(Operator::Plus),
+            '-' => TokenKind::Operator(Operator::Minus),
+            '*' => TokenKind::Operator(Operator::Star),
+            '%' => TokenKind::Operator(Operator::Percent),
+            other => return Some(Err(FinchError::Unexpected(other)))
+        };
+        Some(Ok(self.token(kind, start)))
+    }
+
+    fn expect_second(&mut self, ch: char, op: Operator, start: usize) -> FinchResult<Token> {
+        match self.peek() {
+            Some(next) if next == ch => {
+                self.bump();
+                Ok(self.token(TokenKind::Operator(op), start))
+            },
+            Some(next) => Err(FinchError::ExpectedFound(ch, next)),
+            None => Err(FinchError::Expected(ch))
+        }
+    }
+
+    fn with_optional_eq(&mut self, without: Operator, with: Operator) -> Operator {
+        if self.is_next('=') {
+            self.bump();
+            with
+        } else { without }
+    }
+
+    fn read_ident(&mut self, start: usize) -> String {
+        while let Some(ch) = self.peek() {
+            if !is_ident_continue(ch) { break; }
+            self.bump();
+        }
+        self.source[start..self.pos].to_string()
+    }
+
+    fn read_string(&mut self, start: usize) -> FinchResult<Token> {
+        let mut res = String::new();
+        while let Some(ch) = self.bump() {
+            match ch {
+                '"' => return Ok(self.token(TokenKind::String(res), start)),
+                '\\' => res.push(self.bump().ok_or(FinchError::Expected('"'))?),
+                _ => res.push(ch)
+            }
+        }
+        Err(FinchError::Expected('"'))
+    }
+
+    // Follows the JS numeric literal grammar: decimals with an optional fraction and exponent,
+    // 0x / 0o / 0b prefixed integers, `_` separators and the `n` BigInt suffix.
+    // The first character of the number has already been consumed.
+    fn read_number(&mut self, start: usize) -> FinchResult<Token> {
+        let first = &self.source[start..self.pos];
+        let mut res = String::new();
+        if first == "0" {
+            let radix = match self.peek() {
+                Some('x') | Some('X') => 16,
+                Some('o') | Some('O') => 8,
+                Some('b') | Some('B') => 2,
+                _ => 10
+            };
+            if radix != 10 {
+                self.bump();
+                self.read_digits(&mut res, radix, false)?;
+                if res.is_empty() {
+                    return Err(FinchError::InvalidNumber);
+                }
+                return self.finish_integer(&res, radix, start);
+            }
+        }
+        res.push_str(first);
+        // A leading 0 can't be followed by a separator, like in `0_1`
+        self.read_digits(&mut res, 10, first != "0" && first != ".")?;
+        // Like in strict mode javascript, integers can't start with a 0 (`017`, `08`)
+        if first == "0" && res.len() > 1 {
+            while self.peek().is_some_and(|next| next == '.' || is_ident_continue(next)) {
+                self.bump();
+            }
+            return Err(FinchError::InvalidNumber);
+        }
+        let mut is_integer = first != ".";
+        if is_integer && self.is_next('.') {
+            self.bump();
+            is_integer = false;
+            res.push('.');
+            self.read_digits(&mut res, 10, false)?;
+        }
+        if self.is_next('e') || self.is_next('E') {
+            self.bump();
+            is_integer = false;
+            res.push('e');
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                self.bump();
+                res.push(sign);
+            }
+            let len = res.len();
+            self.read_digits(&mut res, 10, false)?;
+            if res.len() == len {
+                return Err(FinchError::InvalidNumber);
+            }
+        }
+        if is_integer {
+            return self.finish_integer(&res, 10, start);
+        }
+        self.ensure_number_end()?;
+        let num = res.parse::<f64>().map_err(|_| FinchError::InvalidNumber)?;
+        Ok(self.token(TokenKind::Number(num), start))
+    }
+
+    fn finish_integer(&mut self, digits: &str, radix: u32, start: usize) -> FinchResult<Token> {
+        let is_bigint = if self.is_next('n') {
+            self.bump();
+            true
+        } else { self.options.bigint_integers };
+        self.ensure_number_end()?;
+        let kind = if is_bigint {
+            TokenKind::BigInt(i128::from_str_radix(digits, radix).map_err(|_| FinchError::InvalidNumber)?)
+        } else if radix == 10 {
+            TokenKind::Number(digits.parse::<f64>().map_err(|_| FinchError::InvalidNumber)?)
+        } else {
+            TokenKind::Number(digits.chars().fold(0.0, |acc, digit| acc * radix as f64 + digit.to_digit(radix).unwrap_or(0) as f64))
+        };
+        Ok(self.token(kind, start))
+    }
+
+    // Separators are only allowed between two digits. `after_digit` tells if a separator can
+    // come before the first digit which is read
+    fn read_digits(&mut self, res: &mut String, radix: u32, mut after_digit: bool) -> FinchResult<()> {
+        while let Some(ch) = self.peek() {
+            match ch {
+                '_' => {
+                    self.bump();
+                    if !after_digit || !self.peek().is_some_and(|next| next.is_digit(radix)) {
+                        // Skip the rest of the literal, so it isn't read as an identifier
+                        while self.peek().is_some_and(|next| next == '_' || is_ident_continue(next)) {
+                            self.bump();
+                        }
+                        return Err(FinchError::InvalidNumber);
+                    }
+                    after_digit = false;
+                },
+                digit if digit.is_digit(radix) => {
+                    res.push(digit);
+                    self.bump();
+                    after_digit = true;
+                },
+                _ => break
+            }
+        }
+        Ok(())
+    }
+
+    // A number literal cannot be directly followed by an identifier or another digit (`3in`, `0b12`)
+    fn ensure_number_end(&mut self) -> FinchResult<()> {
+        match self.peek() {
+            Some(ch) if is_ident_continue(ch) => {
+                while self.peek().map(is_ident_continue) == Some(true) {
+                    self.bump();
+                }
+                Err(FinchError::InvalidNumber)
+            },
+            _ => Ok(())
+        }
+    }
+
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.in_tag {
+            while let Some(ch) = self.peek() {
+                if !ch.is_whitespace() { break; }
+                self.bump();
+            }
+            let start = self.pos;
+            self.next_in_tag(start).map(|res| res.map_err(|error| Diagnostic { error, severity: Severity::Error, span: start..self.pos }))
+        } else {
+            self.next_text().map(Ok)
+        }
+    }
+}
+
+// Identifiers follow the JS rules: XID_Start / XID_Continue, plus `$` and `_`
+pub fn is_ident_start(ch: char) -> bool {
+    ch == '$' || ch == '_' || ch.is_xid_start()
+}
+
+pub fn is_ident_continue(ch: char) -> bool {
+    ch == '$' || ch == '\u{200C}' || ch == '\u{200D}' || ch.is_xid_continue()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(literal: &str, options: ParserOptions) -> FinchResult<TokenKind> {
+        let source = format!("{{{{{}}}}}", literal);
+        let mut lexer = Lexer::new(&source, options);
+        lexer.next();
+        let token = lexer.next().unwrap().map_err(|diagnostic| diagnostic.error)?;
+        assert_eq!(lexer.next().map(|token| token.unwrap().kind), Some(TokenKind::CloseTag), "{} wasn't read as one token", literal);
+        Ok(token.kind)
+    }
+
+    fn tokens(source: &str) -> Vec<(TokenKind, Range<usize>)> {
+        Lexer::new(source, ParserOptions::default()).map(|token| {
+            let token = token.unwrap();
+            (token.kind, token.span)
+        }).collect()
+    }
+
+    fn ident(name: &str) -> TokenKind {
+        TokenKind::Ident(name.to_string())
+    }
+
+    #[test]
+    fn token_stream() {
+        assert_eq!(tokens("<p>{{#each xs x}}{{x.0 + -1}}{{/}}"), vec![
+            (TokenKind::Text, 0..3), (TokenKind::OpenTag, 3..5), (TokenKind::Hash, 5..6), (ident("each"), 6..10), (ident("xs"), 11..13),
+            (ident("x"), 14..15), (TokenKind::CloseTag, 15..17), (TokenKind::OpenTag, 17..19), (ident("x"), 19..20), (TokenKind::Dot, 20..21),
+            (ident("0"), 21..22), (TokenKind::Operator(Operator::Plus), 23..24), (TokenKind::Operator(Operator::Minus), 25..26),
+            (TokenKind::Number(1.0), 26..27), (TokenKind::CloseTag, 27..29), (TokenKind::OpenTag, 29..31), (TokenKind::Slash, 31..32), (TokenKind::CloseTag, 32..34)
+        ]);
+        let kinds = |source| tokens(source).into_iter().map(|(kind, _)| kind).collect::<Vec<TokenKind>>();
+        assert_eq!(kinds("{{a!==b}}")[2], TokenKind::Operator(Operator::StrictNotEq));
+        assert_eq!(kinds("{{#h a=\"x\" /}}")[3..7], [ident("a"), TokenKind::Assign, TokenKind::String(String::from("x")), TokenKind::Slash]);
+        // After an operand / divides, before a closing }} it ends a block without a body
+        assert_eq!(kinds("{{a / b}}")[2], TokenKind::Operator(Operator::Slash));
+        assert_eq!(kinds("{{#h a / }}")[4], TokenKind::Slash);
+        // An unclosed tag ends where the next one starts
+        assert_eq!(kinds("{{a {{b}}"), [TokenKind::OpenTag, ident("a"), TokenKind::OpenTag, ident("b"), TokenKind::CloseTag]);
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        for name in ["名前", "café", "cafe\u{301}", "$", "_a1", "a$b", "Ωμέγα", "𝑥", "x\u{200D}y", "ſ"] {
+            assert_eq!(tokens(&format!("{{{{{}}}}}", name))[1], (ident(name), 2..2 + name.len()), "{:?}", name);
+        }
+        // Members can start with any identifier character, not only digits
+        assert_eq!(tokens("{{a.١}}")[3].0, ident("١"));
+        // Unicode whitespace separates tokens
+        assert_eq!(tokens("{{a\u{3000}b\u{a0}}}").into_iter().map(|(kind, _)| kind).collect::<Vec<TokenKind>>(), [TokenKind::OpenTag, ident("a"), ident("b"), TokenKind::CloseTag]);
+        for source in ["{{١}}", "{{\u{301}a}}", "{{😀}}", "{{\u{200D}}}"] {
+            let first = Lexer::new(source, ParserOptions::default()).nth(1).unwrap();
+            assert!(matches!(first, Err(Diagnostic { error: FinchError::Unexpected(_), .. })), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn decimals() {
+        for (literal, value) in [("12", 12.0), ("0", 0.0), ("0.5", 0.5), ("0e5", 0.0), ("1_000", 1000.0), ("1.5", 1.5), (".5", 0.5), ("5.", 5.0), ("1e3", 1000.0), ("2.5E-2", 0.025), ("1_0.2_5e+1_0", 10.25e10)] {
+            assert_eq!(number(literal, ParserOptions::default()).unwrap(), TokenKind::Number(value), "{}", literal);
+        }
+    }
+
+    #[test]
+    fn radixes() {
+        for (literal, value) in [("0x1F", 31.0), ("0XfF", 255.0), ("0o17", 15.0), ("0b101", 5.0), ("0x0_1", 1.0), ("0b1_0", 2.0)] {
+            assert_eq!(number(literal, ParserOptions::default()).unwrap(), TokenKind::Number(value), "{}", literal);
+        }
+    }
+
+    #[test]
+    fn bigints() {
+        assert_eq!(number("10n", ParserOptions::default()).unwrap(), TokenKind::BigInt(10));
+        assert_eq!(number("0xffn", ParserOptions::default()).unwrap(), TokenKind::BigInt(255));
+        assert_eq!(number("170141183460469231731687303715884105727n", ParserOptions::default()).unwrap(), TokenKind::BigInt(i128::MAX));
+        let options = ParserOptions { bigint_integers: true };
+        assert_eq!(number("42", options).unwrap(), TokenKind::BigInt(42));
+        assert_eq!(number("0b11", options).unwrap(), TokenKind::BigInt(3));
+        assert_eq!(number("4.2", options).unwrap(), TokenKind::Number(4.2));
+        assert_eq!(number("1e2", options).unwrap(), TokenKind::Number(100.0));
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        for literal in ["017", "08", "00", "01.5", "0_1", "1_", "1__0", "0x_1", "0x1_", "1_.5", "1._5", "1e_5", "0x", "1e", "1e+", "12abc", "1.5n", "170141183460469231731687303715884105728n"] {
+            assert!(matches!(number(literal, ParserOptions::default()), Err(FinchError::InvalidNumber)), "{} should be invalid", literal);
+        }
+    }
+
+}
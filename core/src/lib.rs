@@ -0,0 +1,21 @@
+//! The parser, syntax tree and renderer of finch. Nothing in here depends on node.js,
+//! the renderer reads host owned data through the [DataSource](value::DataSource) trait.
+pub mod ast;
+pub mod visit;
+pub mod parser;
+pub mod lexer;
+pub mod error;
+pub mod format;
+pub mod value;
+pub mod memory;
+pub mod compiler;
+pub mod program;
+pub mod optimize;
+pub mod helper;
+pub mod transpiler;
+pub mod bundle;
+mod default_helpers;
+
+pub use compiler::{Compiler, CompilerContext};
+pub use helper::{Helper, HelperContext};
+pub use value::{RawValue, DataSource, HostRef, OwnedSource};
@@ -0,0 +1,100 @@
+//! The variables of a render. Variables bound by blocks like `each` live in slots, which are
+//! resolved when a template is lowered, so reading one doesn't look it up by name. Slots are
+//! grouped into scopes. Values read from the data are remembered by their path, so a template
+//! which uses `user.name` twice only reads it once. Paths which start at a bound variable are
+//! remembered by its slot, and forgotten when the variable changes.
+
+use crate::value::RawValue;
+use std::collections::HashMap;
+
+struct Binding {
+    value: RawValue,
+    // Values read from the value, by their path like `user.name`
+    paths: HashMap<String, RawValue>
+}
+
+pub struct Memory {
+    // The bound variables, the innermost ones last
+    slots: Vec<Binding>,
+    // The first slot of every scope
+    scopes: Vec<usize>,
+    // Values read from the data, which doesn't change during a render
+    lookups: HashMap<String, RawValue>
+}
+
+impl Memory {
+
+    pub fn new() -> Self {
+        Self {
+            slots: vec![],
+            scopes: vec![0],
+            lookups: HashMap::new()
+        }
+    }
+
+    /// Starts a new scope.
+    pub fn extend(&mut self) {
+        self.scopes.push(self.slots.len());
+    }
+
+    /// Leaves the current scope, unbinding the variables set in it.
+    pub fn destroy(&mut self) {
+        if let Some(start) = self.scopes.pop() {
+            self.slots.truncate(start);
+        }
+        if self.scopes.is_empty() {
+            self.scopes.push(0);
+        }
+    }
+
+    /// The value of the variable in `slot`, undefined if it wasn't set.
+    pub fn value(&self, slot: usize) -> RawValue {
+        self.slots.get(slot).map_or(RawValue::Undefined, |binding| binding.value.clone())
+    }
+
+    /// Binds a variable to a slot, the slots before it which weren't set yet are undefined.
+    pub fn set(&mut self, slot: usize, value: RawValue) {
+        if slot < self.slots.len() {
+            self.replace(slot, value);
+            return;
+        }
+        while self.slots.len() < slot {
+            self.slots.push(Binding { value: RawValue::Undefined, paths: HashMap::new() });
+        }
+        self.slots.push(Binding { value, paths: HashMap::new() });
+    }
+
+    /// Changes the value of a bound variable, what was read from the old value is forgotten.
+    pub fn replace(&mut self, slot: usize, value: RawValue) {
+        let binding = &mut self.slots[slot];
+        binding.value = value;
+        binding.paths.clear();
+    }
+
+    /// A remembered value of a path, which starts at the variable in `slot`, or at the data if it's `None`.
+    pub fn lookup(&self, slot: Option<usize>, path: &str) -> Option<&RawValue> {
+        match slot {
+            Some(slot) => self.slots.get(slot)?.paths.get(path),
+            None => self.lookups.get(path)
+        }
+    }
+
+    /// Remembers the value of a path, see [lookup](Self::lookup).
+    pub fn remember(&mut self, slot: Option<usize>, path: String, value: RawValue) {
+        match slot {
+            Some(slot) => if let Some(binding) = self.slots.get_mut(slot) {
+                binding.paths.insert(path, value);
+            },
+            None => {
+                self.lookups.insert(path, value);
+            }
+        }
+    }
+
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
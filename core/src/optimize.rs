@@ -0,0 +1,214 @@
+//! The optimizations which run on a template when it's added to a [Compiler](crate::Compiler).
+//! Expressions which only use literals are evaluated once, expression tags which become
+//! constant are turned into static text, and adjacent static text is merged. `if` branches
+//! which can never run are skipped when the template is [lowered](crate::program::Program).
+
+use std::cmp::Ordering;
+use std::ops::Range;
+use crate::ast::*;
+use crate::error::{Diagnostic, FinchError, Severity};
+use crate::value::{arithmetic, compare_values, Arithmetic, OwnedSource, RawValue};
+use crate::visit::{walk_expression_mut, walk_subtext_mut, walk_template_mut, VisitorMut};
+
+/// Optimizes a parsed template in place, and returns warnings about the conditions of `if`,
+/// `unless`, `with`, `and` and `or` which are always true or always false.
+pub fn optimize(ast: &mut SubText) -> Vec<Diagnostic> {
+    let mut folder = Folder { warnings: vec![] };
+    folder.visit_subtext_mut(ast);
+    folder.warnings
+}
+
+/// The value of an expression which doesn't depend on the data, `None` if it does,
+/// or if evaluating it fails.
+pub fn constant(exp: &ExpressionKind) -> Option<RawValue> {
+    match exp {
+        ExpressionKind::String(val) => Some(RawValue::String(val.clone())),
+        ExpressionKind::Number(val) => Some(RawValue::Number(*val)),
+        ExpressionKind::BigInt(val) => Some(RawValue::BigInt(*val)),
+        ExpressionKind::Bool(val) => Some(RawValue::Boolean(*val)),
+        ExpressionKind::Undefined => Some(RawValue::Undefined),
+        ExpressionKind::Null => Some(RawValue::Null),
+        ExpressionKind::Unary(op) => {
+            let val = constant(op.operand())?;
+            Some(match &**op {
+                UnaryOps::Not(_) => RawValue::Boolean(val.is_falsey()),
+                UnaryOps::Neg(_) => match val {
+                    RawValue::BigInt(num) => RawValue::BigInt(num.checked_neg()?),
+                    val => RawValue::Number(-val.to_number())
+                }
+            })
+        },
+        ExpressionKind::Binary(op) => {
+            let (left, right) = op.operands();
+            // Like in the renderer, the right side isn't needed if the left side decides the result
+            match &**op {
+                BinaryOps::And(_, _) => {
+                    let left = constant(left)?;
+                    if left.is_falsey() {
+                        return Some(RawValue::Boolean(false));
+                    }
+                    let right = constant(right)?;
+                    Some(if right.is_falsey() { RawValue::Boolean(false) } else { right })
+                },
+                BinaryOps::Or(_, _) => {
+                    let left = constant(left)?;
+                    if !left.is_falsey() {
+                        return Some(left);
+                    }
+                    let right = constant(right)?;
+                    Some(if right.is_falsey() { RawValue::Boolean(false) } else { right })
+                },
+                _ => {
+                    let (left, right) = (constant(left)?, constant(right)?);
+                    let calculate = |op| arithmetic(op, &left, &right, &mut OwnedSource).ok();
+                    Some(match &**op {
+                        BinaryOps::Compare(_, _) => RawValue::Boolean(left.loose_equals(&right, &mut OwnedSource).ok()?),
+                        BinaryOps::Not(_, _) => RawValue::Boolean(!left.loose_equals(&right, &mut OwnedSource).ok()?),
+                        BinaryOps::StrictCompare(_, _) => RawValue::Boolean(left.strict_equals(&right, &mut OwnedSource).ok()?),
+                        BinaryOps::StrictNot(_, _) => RawValue::Boolean(!left.strict_equals(&right, &mut OwnedSource).ok()?),
+                        BinaryOps::Gt(_, _) => RawValue::Boolean(compare_values(&left, &right).ok()? == Ordering::Greater),
+                        BinaryOps::Lt(_, _) => RawValue::Boolean(compare_values(&left, &right).ok()? == Ordering::Less),
+                        BinaryOps::Gte(_, _) => RawValue::Boolean(compare_values(&left, &right).ok()? != Ordering::Less),
+                        BinaryOps::Lte(_, _) => RawValue::Boolean(compare_values(&left, &right).ok()? != Ordering::Greater),
+                        BinaryOps::Add(_, _) => calculate(Arithmetic::Add)?,
+                        BinaryOps::Sub(_, _) => calculate(Arithmetic::Sub)?,
+                        BinaryOps::Mul(_, _) => calculate(Arithmetic::Mul)?,
+                        BinaryOps::Div(_, _) => calculate(Arithmetic::Div)?,
+                        BinaryOps::Rem(_, _) => calculate(Arithmetic::Rem)?,
+                        BinaryOps::And(_, _) | BinaryOps::Or(_, _) => unreachable!()
+                    })
+                }
+            }
+        },
+        ExpressionKind::Var(_) | ExpressionKind::VarDot(_) | ExpressionKind::Call { .. } => None
+    }
+}
+
+fn literal(val: RawValue) -> Option<ExpressionKind> {
+    match val {
+        RawValue::String(val) => Some(ExpressionKind::String(val)),
+        RawValue::Number(val) => Some(ExpressionKind::Number(val)),
+        RawValue::BigInt(val) => Some(ExpressionKind::BigInt(val)),
+        RawValue::Boolean(val) => Some(ExpressionKind::Bool(val)),
+        RawValue::Undefined => Some(ExpressionKind::Undefined),
+        RawValue::Null => Some(ExpressionKind::Null),
+        RawValue::Vec(_) | RawValue::Object(_) | RawValue::HostObject(_) | RawValue::HostArray(_) | RawValue::Function(_) => None
+    }
+}
+
+struct Folder {
+    warnings: Vec<Diagnostic>
+}
+
+impl Folder {
+
+    // Warns about the conditions of the built-in helpers which don't depend on the data: the first
+    // parameter of `if`, `unless` and `with`, and every parameter of `and` and `or`
+    fn check_conditions(&mut self, block: &FnBlock, pos: &Range<usize>) {
+        let mut link = Some(block);
+        while let Some(block) = link {
+            let conditions = match block.name.as_str() {
+                "if" | "unless" | "with" => &block.params[..block.params.len().min(1)],
+                "and" | "or" => &block.params[..],
+                _ => &[]
+            };
+            for val in conditions.iter().filter_map(constant) {
+                self.warnings.push(Diagnostic {
+                    error: FinchError::ConstantCondition(!val.is_falsey()),
+                    severity: Severity::Warning,
+                    span: pos.clone()
+                });
+            }
+            link = block.chain.as_deref();
+        }
+    }
+
+}
+
+impl VisitorMut for Folder {
+
+    fn visit_subtext_mut(&mut self, text: &mut SubText) {
+        walk_subtext_mut(self, text);
+        let mut templates: Vec<Template> = Vec::with_capacity(text.templates.len());
+        for template in text.templates.drain(..) {
+            if let TemplateKind::Text(next) = &template.kind {
+                if next.is_empty() {
+                    continue;
+                }
+                if let Some(Template { kind: TemplateKind::Text(prev), pos }) = templates.last_mut() {
+                    prev.push_str(next);
+                    pos.end = template.pos.end;
+                    continue;
+                }
+            }
+            templates.push(template);
+        }
+        text.templates = templates;
+    }
+
+    fn visit_template_mut(&mut self, template: &mut Template) {
+        walk_template_mut(self, template);
+        match &template.kind {
+            TemplateKind::Expression(exp) => if let Some(val) = constant(exp) {
+                template.kind = TemplateKind::Text(val.into_string());
+            },
+            TemplateKind::Block(block) => self.check_conditions(block, &template.pos),
+            TemplateKind::Text(_) | TemplateKind::Error => {}
+        }
+    }
+
+    fn visit_expression_mut(&mut self, exp: &mut ExpressionKind) {
+        walk_expression_mut(self, exp);
+        if matches!(exp, ExpressionKind::Unary(_) | ExpressionKind::Binary(_)) {
+            if let Some(folded) = constant(exp).and_then(literal) {
+                *exp = folded;
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserOptions};
+
+    // The warnings as (always truthy, span)
+    fn warnings(source: &str) -> Vec<(bool, Range<usize>)> {
+        let mut ast = Parser::parse(source, ParserOptions::default()).unwrap();
+        optimize(&mut ast).into_iter().map(|diagnostic| match diagnostic {
+            Diagnostic { error: FinchError::ConstantCondition(val), severity: Severity::Warning, span } => (val, span),
+            other => panic!("{:?}", other)
+        }).collect()
+    }
+
+    #[test]
+    fn warns_about_constant_conditions() {
+        assert_eq!(warnings("{{#if 1 + 1 == 2}}x{{/#if a}}y{{/#if \"\"}}z{{/}}"), [(true, 0..47), (false, 0..47)]);
+        assert_eq!(warnings("{{#unless null}}x{{/#else}}y{{/}}"), [(false, 0..33)]);
+        assert_eq!(warnings("{{#with 0 as x}}{{x}}{{/}}"), [(false, 0..26)]);
+        assert_eq!(warnings("{{#and a !0 b}}x{{/}} {{#or 2n * 0n a}}y{{/}}"), [(true, 0..21), (false, 22..45)]);
+        // Inside bodies too
+        assert_eq!(warnings("{{#each xs x}}{{#if true || x}}{{/}}{{/}}"), [(true, 14..36)]);
+    }
+
+    #[test]
+    fn no_warnings_for_data() {
+        assert!(warnings("{{#if a}}{{/}}{{#unless a.b}}{{/}}{{#with f(1) x}}{{/}}{{#and a b}}{{/}}{{#or a || b}}{{/}}").is_empty());
+        // Only the condition of with is checked, and helpers which aren't conditions aren't
+        assert!(warnings("{{#with a as x}}{{/}}{{#each xs x where=true}}{{/}}{{#range 1 2 i}}{{/}}{{#switch 1}}{{/#case 1}}{{/}}").is_empty());
+    }
+
+    #[test]
+    fn folds_constant_expressions() {
+        let mut ast = Parser::parse("a{{1 + 2}}b{{x + (2 * 3)}}{{\"s\" + 1n}}", ParserOptions::default()).unwrap();
+        optimize(&mut ast);
+        let kinds = ast.templates.into_iter().map(|template| template.kind).collect::<Vec<TemplateKind>>();
+        assert_eq!(kinds, [
+            TemplateKind::Text(String::from("a3b")),
+            TemplateKind::Expression(ExpressionKind::Binary(Box::new(BinaryOps::Add(ExpressionKind::Var(String::from("x")), ExpressionKind::Number(6.0))))),
+            TemplateKind::Text(String::from("s1"))
+        ]);
+    }
+
+}
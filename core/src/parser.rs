@@ -0,0 +1,580 @@
+
+use std::ops::Range;
+use crate::error::*;
+use crate::lexer::*;
+use crate::ast::*;
+
+static OR_PREC: i8 = 1;
+static AND_PREC: i8 = 2;
+static EQUALITY_PREC: i8 = 3;
+static COMPARE_PREC: i8 = 4;
+static ADDITIVE_PREC: i8 = 5;
+static MULTIPLICATIVE_PREC: i8 = 6;
+
+#[derive(Default, Clone, Copy)]
+pub struct ParserOptions {
+    /// Integer literals without a fraction or exponent become BigInts
+    pub bigint_integers: bool
+}
+
+pub struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    last_span: Range<usize>,
+    // When set, errors are collected into `diagnostics` instead of stopping the parse
+    recover: bool,
+    diagnostics: Vec<Diagnostic>,
+    // Set while parsing the space separated parameters of a block, see `starts_negative_param`
+    in_params: bool
+}
+
+impl<'a> Parser<'a> {
+
+    fn new(source: &'a str, tokens: Vec<Token>, recover: bool, diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            source,
+            tokens,
+            pos: 0,
+            last_span: 0..0,
+            recover,
+            diagnostics,
+            in_params: false
+        }
+    }
+
+    pub fn parse(str: &'a str, options: ParserOptions) -> FinchResult<SubText> {
+        let tokens = Lexer::new(str, options).collect::<Result<Vec<Token>, Diagnostic>>().map_err(|diagnostic| diagnostic.error)?;
+        Self::new(str, tokens, false, vec![]).parse_root()
+    }
+
+    /// Parses the whole template even if it contains errors. Tags which fail to parse become
+    /// [TemplateKind::Error] nodes and the parser continues after their closing `}}`.
+    pub fn parse_recovering(str: &'a str, options: ParserOptions) -> (SubText, Vec<Diagnostic>) {
+        let mut diagnostics = vec![];
+        let tokens = Lexer::new(str, options).map(|res| match res {
+            Ok(token) => token,
+            Err(diagnostic) => {
+                let span = diagnostic.span.clone();
+                diagnostics.push(diagnostic);
+                Token { kind: TokenKind::Invalid, span }
+            }
+        }).collect();
+        let mut p = Self::new(str, tokens, true, diagnostics);
+        let root = p.parse_root().unwrap_or(SubText { pos: 0..str.len(), templates: vec![] });
+        p.diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        (root, p.diagnostics)
+    }
+
+    fn parse_root(&mut self) -> FinchResult<SubText> {
+        let mut templates: Vec<Template> = vec![];
+        while let Some(token) = self.next() {
+            if token.kind == TokenKind::Text {
+                templates.push(self.text(token));
+                continue;
+            } else if token.kind != TokenKind::OpenTag {
+                continue;
+            }
+            let start = token.span.start;
+            if self.is_next(&TokenKind::Slash) {
+                self.next();
+                self.fail(FinchError::Unexpected('/'), start)?;
+                let end = self.synchronize(start);
+                templates.push(Template { pos: start..end, kind: TemplateKind::Error });
+                continue;
+            }
+            templates.push(self.parse_template(start)?);
+        }
+        Ok(SubText {
+            pos: 0..self.source.len(),
+            templates
+        })
+    }
+
+    // Parses the contents of a tag, right after the {{
+    fn parse_template(&mut self, start: usize) -> FinchResult<Template> {
+        if self.is_next(&TokenKind::Hash) {
+            self.next();
+            return self.parse_block(start);
+        }
+        match self.parse_expression_tag() {
+            Ok((end, exp)) => Ok(Template { pos: start..end, kind: TemplateKind::Expression(exp) }),
+            Err(err) => {
+                self.fail(err, start)?;
+                let end = self.synchronize(start);
+                Ok(Template { pos: start..end, kind: TemplateKind::Error })
+            }
+        }
+    }
+
+    fn text(&self, token: Token) -> Template {
+        Template { kind: TemplateKind::Text(self.source[token.span.clone()].to_string()), pos: token.span }
+    }
+
+    fn parse_expression_tag(&mut self) -> FinchResult<(usize, ExpressionKind)> {
+        let exp = self.parse_expression()?;
+        Ok((self.skip_close_tag()?, exp))
+    }
+
+    // Parses text INSIDE a function block, up until the closing {{/}} or followup {{/#...}}
+    fn parse_text(&mut self, start: usize, block: &Template) -> FinchResult<(usize, SubText, Option<Box<FnBlock>>)> {
+        let mut templates: Vec<Template> = vec![];
+        while let Some(token) = self.next() {
+            if token.kind == TokenKind::Text {
+                templates.push(self.text(token));
+                continue;
+            } else if token.kind != TokenKind::OpenTag {
+                continue;
+            }
+            if !self.is_next(&TokenKind::Slash) {
+                templates.push(self.parse_template(token.span.start)?);
+                continue;
+            }
+            self.next();
+            let text = SubText { pos: start..token.span.start, templates };
+            if self.is_next(&TokenKind::Hash) {
+                self.next();
+                let chain = self.parse_block(token.span.start)?;
+                let end = chain.pos.end;
+                return Ok((end, text, match chain.kind {
+                    TemplateKind::Block(chain) => Some(Box::from(chain)),
+                    _ => None
+                }));
+            }
+            let end = match self.skip_close_tag() {
+                Ok(end) => end,
+                Err(err) => {
+                    self.fail(err, token.span.start)?;
+                    self.synchronize(token.span.start)
+                }
+            };
+            return Ok((end, text, None));
+        }
+        let name = match &block.kind {
+            TemplateKind::Block(block) => block.name.clone(),
+            _ => String::new()
+        };
+        self.last_span = block.pos.clone();
+        self.fail(FinchError::UnclosedBlock(name), block.pos.start)?;
+        Ok((self.source.len(), SubText { pos: start..self.source.len(), templates }, None))
+    }
+
+    // Parses a block right after the {{#, including its body and followups
+    fn parse_block(&mut self, start: usize) -> FinchResult<Template> {
+        let (header_end, has_body, mut template) = match self.parse_block_header() {
+            Ok((end, has_body, block)) => (end, has_body, Template { pos: start..end, kind: TemplateKind::Block(block) }),
+            Err(err) => {
+                // A tag without a name, like `{{#}}`, isn't treated as a block
+                let named = !matches!(err, FinchError::MissingBlockName);
+                self.fail(err, start)?;
+                let end = self.synchronize(start);
+                // Whether the broken tag still looks like it opens a body
+                let has_body = named && self.pos >= 2 && self.tokens[self.pos - 1].kind == TokenKind::CloseTag && self.tokens[self.pos - 2].kind != TokenKind::Slash;
+                (end, has_body, Template { pos: start..end, kind: TemplateKind::Error })
+            }
+        };
+        if has_body {
+            let (end, body, chain) = self.parse_text(header_end, &template)?;
+            template.pos.end = end;
+            if let TemplateKind::Block(block) = &mut template.kind {
+                block.block = Some(body);
+                block.chain = chain;
+            }
+        }
+        Ok(template)
+    }
+
+    // Returns the end of the tag and whether the block has a body
+    fn parse_block_header(&mut self) -> FinchResult<(usize, bool, FnBlock)> {
+        let fn_name = match self.next() {
+            Some(Token { kind: TokenKind::Ident(name), .. }) => name,
+            None | Some(Token { kind: TokenKind::CloseTag | TokenKind::Slash | TokenKind::OpenTag, .. }) => return Err(FinchError::MissingBlockName),
+            other => return Err(self.unexpected(other.as_ref()))
+        };
+        let mut params: Vec<ExpressionKind> = vec![];
+        let mut hash: Vec<(String, ExpressionKind)> = vec![];
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::Comma => {
+                    self.next();
+                },
+                TokenKind::Slash => {
+                    self.next();
+                    let end = self.skip_close_tag()?;
+                    return Ok((end, false, FnBlock {
+                        name: fn_name,
+                        params,
+                        hash,
+                        block: None,
+                        chain: None
+                    }))
+                },
+                TokenKind::CloseTag => {
+                    let end = token.span.end;
+                    self.next();
+                    return Ok((end, true, FnBlock {
+                        name: fn_name,
+                        params,
+                        hash,
+                        block: None,
+                        chain: None
+                    }))
+                },
+                TokenKind::Ident(_) if matches!(self.tokens.get(self.pos + 1), Some(Token { kind: TokenKind::Assign, .. })) => {
+                    let key = match self.next() {
+                        Some(Token { kind: TokenKind::Ident(key), .. }) => key,
+                        _ => unreachable!()
+                    };
+                    self.next();
+                    hash.push((key, self.parse_param()?));
+                },
+                _ => params.push(self.parse_param()?)
+            }
+        }
+        Err(FinchError::Expected('}'))
+    }
+
+    pub fn parse_expression(&mut self) -> FinchResult<ExpressionKind> {
+        self.parse_binary(0)
+    }
+
+    fn parse_param(&mut self) -> FinchResult<ExpressionKind> {
+        self.in_params = true;
+        let res = self.parse_expression();
+        self.in_params = false;
+        res
+    }
+
+    // Parses an expression in parentheses, where spaces don't separate parameters
+    fn parse_nested(&mut self) -> FinchResult<ExpressionKind> {
+        let in_params = std::mem::replace(&mut self.in_params, false);
+        let res = self.parse_expression();
+        self.in_params = in_params;
+        res
+    }
+
+    // A `-` with whitespace before it but not after it starts a new, negative parameter
+    // instead of subtracting: `{{#range 1 -5 i}}`, while `{{#range 1 - x i}}` subtracts
+    fn starts_negative_param(&self) -> bool {
+        let minus = &self.tokens[self.pos].span;
+        self.in_params && self.source[..minus.start].ends_with(char::is_whitespace) &&
+            self.tokens.get(self.pos + 1).is_some_and(|next| next.span.start == minus.end)
+    }
+
+    // Precedence climbing, all binary operators are left-associative
+    fn parse_binary(&mut self, min_prec: i8) -> FinchResult<ExpressionKind> {
+        let mut left = self.parse_unary()?;
+        while let Some(TokenKind::Operator(op)) = self.peek().map(|token| &token.kind) {
+            let op = *op;
+            let prec = match op {
+                Operator::Or => OR_PREC,
+                Operator::And => AND_PREC,
+                Operator::Eq | Operator::NotEq | Operator::StrictEq | Operator::StrictNotEq => EQUALITY_PREC,
+                Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte => COMPARE_PREC,
+                Operator::Minus if self.starts_negative_param() => break,
+                Operator::Plus | Operator::Minus => ADDITIVE_PREC,
+                Operator::Star | Operator::Slash | Operator::Percent => MULTIPLICATIVE_PREC,
+                Operator::Not => break
+            };
+            if prec < min_prec {
+                break;
+            }
+            self.next();
+            let right = self.parse_binary(prec + 1)?;
+            left = ExpressionKind::Binary(Box::new(match op {
+                Operator::Eq => BinaryOps::Compare(left, right),
+                Operator::NotEq => BinaryOps::Not(left, right),
+                Operator::StrictEq => BinaryOps::StrictCompare(left, right),
+                Operator::StrictNotEq => BinaryOps::StrictNot(left, right),
+                Operator::Gt => BinaryOps::Gt(left, right),
+                Operator::Gte => BinaryOps::Gte(left, right),
+                Operator::Lt => BinaryOps::Lt(left, right),
+                Operator::Lte => BinaryOps::Lte(left, right),
+                Operator::And => BinaryOps::And(left, right),
+                Operator::Or => BinaryOps::Or(left, right),
+                Operator::Plus => BinaryOps::Add(left, right),
+                Operator::Minus => BinaryOps::Sub(left, right),
+                Operator::Star => BinaryOps::Mul(left, right),
+                Operator::Slash => BinaryOps::Div(left, right),
+                Operator::Percent => BinaryOps::Rem(left, right),
+                Operator::Not => unreachable!()
+            }));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> FinchResult<ExpressionKind> {
+        match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Operator(Operator::Not)) => {
+                self.next();
+                Ok(ExpressionKind::Unary(Box::new(UnaryOps::Not(self.parse_unary()?))))
+            },
+            Some(TokenKind::Operator(Operator::Minus)) => {
+                self.next();
+                Ok(match self.parse_unary()? {
+                    ExpressionKind::Number(num) => ExpressionKind::Number(-num),
+                    ExpressionKind::BigInt(num) => ExpressionKind::BigInt(-num),
+                    exp => ExpressionKind::Unary(Box::new(UnaryOps::Neg(exp)))
+                })
+            },
+            _ => self.parse_call()
+        }
+    }
+
+    fn parse_call(&mut self) -> FinchResult<ExpressionKind> {
+        let mut res = self.parse_primary()?;
+        while self.is_next(&TokenKind::OpenParen) {
+            self.next();
+            let mut params: Vec<ExpressionKind> = vec![];
+            loop {
+                match self.peek().map(|token| &token.kind) {
+                    Some(TokenKind::Comma) => {
+                        self.next();
+                    },
+                    Some(TokenKind::CloseParen) => {
+                        self.next();
+                        break;
+                    },
+                    Some(_) => params.push(self.parse_nested()?),
+                    None => return Err(FinchError::Expected(')'))
+                }
+            }
+            res = ExpressionKind::Call {
+                var: Box::from(res),
+                params
+            };
+        }
+        Ok(res)
+    }
+
+    fn parse_primary(&mut self) -> FinchResult<ExpressionKind> {
+        let token = self.next();
+        match token.as_ref().map(|token| &token.kind) {
+            Some(TokenKind::String(str)) => Ok(ExpressionKind::String(str.clone())),
+            Some(TokenKind::Number(num)) => Ok(ExpressionKind::Number(*num)),
+            Some(TokenKind::BigInt(num)) => Ok(ExpressionKind::BigInt(*num)),
+            Some(TokenKind::Ident(name)) => self.parse_possible_var(name.clone()),
+            Some(TokenKind::OpenParen) => {
+                let exp = self.parse_nested()?;
+                match self.next() {
+                    Some(Token { kind: TokenKind::CloseParen, .. }) => Ok(exp),
+                    Some(other) => Err(FinchError::ExpectedFound(')', self.first_char(&other))),
+                    None => Err(FinchError::Expected(')'))
+                }
+            },
+            _ => Err(self.unexpected(token.as_ref()))
+        }
+    }
+
+    fn parse_possible_var(&mut self, first: String) -> FinchResult<ExpressionKind> {
+        if !self.is_next(&TokenKind::Dot) {
+            return Ok(match first.as_str() {
+                "true" => ExpressionKind::Bool(true),
+                "false" => ExpressionKind::Bool(false),
+                "undefined" => ExpressionKind::Undefined,
+                "null" => ExpressionKind::Null,
+                "Infinity" => ExpressionKind::Number(f64::INFINITY),
+                "NaN" => ExpressionKind::Number(f64::NAN),
+                _ => ExpressionKind::Var(first)
+            });
+        }
+        let mut vault: Vec<String> = vec![first];
+        while self.is_next(&TokenKind::Dot) {
+            self.next();
+            match self.next() {
+                Some(Token { kind: TokenKind::Ident(name), .. }) => vault.push(name),
+                _ => return Err(FinchError::MissingPropName)
+            }
+        }
+        Ok(ExpressionKind::VarDot(vault))
+    }
+
+    fn skip_close_tag(&mut self) -> FinchResult<usize> {
+        match self.next() {
+            Some(Token { kind: TokenKind::CloseTag, span }) => Ok(span.end),
+            Some(other) => Err(FinchError::ExpectedFound('}', self.first_char(&other))),
+            None => Err(FinchError::Expected('}'))
+        }
+    }
+
+    // Either returns the error, or records it when recovering. Errors in tags which contain
+    // a token the lexer failed to read are not recorded, the lexer already reported them.
+    fn fail(&mut self, error: FinchError, tag_start: usize) -> FinchResult<()> {
+        if !self.recover {
+            return Err(error);
+        }
+        let end = self.last_span.end;
+        let lexer_failed = self.tokens.iter().any(|token| token.kind == TokenKind::Invalid && token.span.start >= tag_start && token.span.end <= end);
+        if !lexer_failed {
+            self.diagnostics.push(Diagnostic { error, severity: Severity::Error, span: self.last_span.clone() });
+        }
+        Ok(())
+    }
+
+    // Skips the rest of a broken tag and returns where it ends. If the error was caused by
+    // the start of the next tag, or the end of this one, that token is given back.
+    fn synchronize(&mut self, tag_start: usize) -> usize {
+        if let Some(token) = self.pos.checked_sub(1).and_then(|ind| self.tokens.get(ind)) {
+            if (token.kind == TokenKind::OpenTag || token.kind == TokenKind::CloseTag) && token.span.start > tag_start {
+                self.pos -= 1;
+            }
+        }
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::CloseTag => {
+                    let end = token.span.end;
+                    self.next();
+                    return end;
+                },
+                TokenKind::OpenTag => return token.span.start,
+                _ => {
+                    self.next();
+                }
+            }
+        }
+        self.source.len()
+    }
+
+    fn unexpected(&self, token: Option<&Token>) -> FinchError {
+        match token {
+            Some(token) => FinchError::Unexpected(self.first_char(token)),
+            None => FinchError::Expected('}')
+        }
+    }
+
+    fn first_char(&self, token: &Token) -> char {
+        self.source[token.span.clone()].chars().next().unwrap_or(' ')
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        if let Some(token) = &token {
+            self.pos += 1;
+            self.last_span = token.span.clone();
+        } else {
+            self.last_span = self.source.len()..self.source.len();
+        }
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn is_next(&self, kind: &TokenKind) -> bool {
+        self.peek().map(|token| &token.kind == kind).unwrap_or(false)
+    }
+
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Templates covering the whole syntax, the formatter tests check they survive formatting
+    pub(crate) const CORPUS: &[&str] = &[
+        "plain text, no tags",
+        "{{#each items item}}{{item}}{{/}}",
+        "{{#if x}}y{{/}}",
+        "{{a + b * c}} {{a - b - c}} {{a % b / c}} {{a + 1 > b * -2}} {{a -b}}",
+        "{{#range 1 -5 i}}{{/}}{{#range a -b i}}{{/}}{{#range a - b i}}{{/}}{{#range a-b i}}{{/}}",
+        "{{#range f(a -b), (a -b) i}}{{/}}{{#helper a / b /}}{{#helper a/}}",
+        "{{ user.name }} {{#if  a==1 }}one{{/#if (a > 2) && !b}}two{{/#else}}{{/}} {{ NaN }}",
+        "<p>{{ name }}</p>\n{{#each items item where=(item.on || x) sort=\"n\"}}\n  {{item.n}}\n{{/}}",
+        "{{a || b && c}} {{(a || b) && c}} {{!(a == b)}} {{a === b}} {{a !== b}} {{a != b}} {{a <= b}} {{a >= b}} {{a < b}}",
+        "{{-(x)}} {{-3}} {{-5n}} {{- -x}} {{!!x}} {{f(1, g(2))(3)}} {{f()}} {{arr.0.name}} {{a.$b._c}}",
+        "{{1_000.5}} {{0x1F}} {{0o17}} {{0b101}} {{0xffn}} {{1e21}} {{2.5E-2}} {{.5}} {{5.}} {{Infinity}} {{-Infinity}} {{-0}} {{0}}",
+        "{{\"say \\\"hi\\\"\"}} {{\"back\\\\slash\"}} {{undefined}} {{null}} {{true}} {{false}}",
+        "{{#let a = 1, b = a == 1}}{{b}}{{/}}{{#shout \"x\" (y)/}}{{#switch k}}{{/#case 1 2}}A{{/#default}}B{{/}}",
+        "{{#with user as u}}{{u.名前}}{{/#else}}none{{/}}{{#range 1 10 step=2 as i}}{{i}}{{/}}{{#times n i}}*{{/}}",
+        "{{#each xs x reverse sort=\"n\" offset=1 limit=2}}{{x}}{{/}}{{#groupBy xs \"k\" as g}}{{g.key}}{{/}}",
+        "{{#and a b}}{{/#else}}{{/}}{{#or a b /}}{{#unless a}}{{/}}{{#deepEqual a b}}{{/}}{{#template \"t\" data /}}",
+        "{{#js}}return data.a * 2{{/}}{{#outer}}{{#inner x}}{{#if y}}deep{{/}}{{/}}{{/}}",
+        "line one\n  {{#if a}}\n    nested\n  {{/#else}}\n    other\n  {{/}}\n"
+    ];
+
+    #[test]
+    fn parses_the_corpus() {
+        for source in CORPUS {
+            assert!(Parser::parse(source, ParserOptions::default()).is_ok(), "{}", source);
+        }
+    }
+
+    fn errors(source: &str) -> Vec<String> {
+        Parser::parse_recovering(source, ParserOptions::default()).1.into_iter().map(|diagnostic| diagnostic.error.to_string()).collect()
+    }
+
+    #[test]
+    fn block_without_name() {
+        assert_eq!(errors("{{#}}text"), vec!["Expected block name after hash (#)"]);
+        assert_eq!(errors("{{#/}}{{#if a}}x{{/}}"), vec!["Expected block name after hash (#)"]);
+        assert!(matches!(Parser::parse("{{#}}", ParserOptions::default()), Err(FinchError::MissingBlockName)));
+    }
+
+    #[test]
+    fn reports_every_error_in_order() {
+        let source = "{{a.}} ok {{b @}} {{#if x}}y{{/}} {{c";
+        let (ast, diagnostics) = Parser::parse_recovering(source, ParserOptions::default());
+        let found = diagnostics.iter().map(|diagnostic| (diagnostic.error.to_string(), diagnostic.line_col(source))).collect::<Vec<_>>();
+        assert_eq!(found, vec![
+            (String::from("Expected property name after dot (.)"), (1, 5)),
+            (String::from("Unexpected character '@'"), (1, 15)),
+            (String::from("Expected character '}'"), (1, 38))
+        ]);
+        // The tags around the errors are kept
+        let kinds = ast.templates.iter().map(|template| match &template.kind {
+            TemplateKind::Text(_) => "text",
+            TemplateKind::Expression(_) => "expression",
+            TemplateKind::Block(_) => "block",
+            TemplateKind::Error => "error"
+        }).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["error", "text", "error", "text", "block", "text", "error"]);
+    }
+
+    #[test]
+    fn recovers_from_unclosed_blocks_and_stray_closes() {
+        assert_eq!(errors("{{#if a}}never closed"), vec!["Block if is never closed with {{/}}"]);
+        assert_eq!(errors("{{/}}text{{0x}}"), vec!["Unexpected character '/'", "Invalid number literal, or a BigInt literal which doesn't fit in a 128-bit signed integer (i128)"]);
+        assert!(errors("{{#each items item}}{{item}}{{/}}").is_empty());
+    }
+
+    #[test]
+    fn arithmetic_precedence() {
+        let parse = |source: &str| match Parser::parse(source, ParserOptions::default()).unwrap().templates.remove(0).kind {
+            TemplateKind::Expression(exp) => exp,
+            other => panic!("{:?}", other)
+        };
+        let var = |name: &str| ExpressionKind::Var(name.to_string());
+        let binary = |op: fn(ExpressionKind, ExpressionKind) -> BinaryOps, left, right| ExpressionKind::Binary(Box::new(op(left, right)));
+        assert_eq!(parse("{{a + b * c}}"), binary(BinaryOps::Add, var("a"), binary(BinaryOps::Mul, var("b"), var("c"))));
+        assert_eq!(parse("{{a - b - c}}"), binary(BinaryOps::Sub, binary(BinaryOps::Sub, var("a"), var("b")), var("c")));
+        assert_eq!(parse("{{a % b / c}}"), binary(BinaryOps::Div, binary(BinaryOps::Rem, var("a"), var("b")), var("c")));
+        assert_eq!(parse("{{a + 1 > b * -2}}"), binary(BinaryOps::Gt, binary(BinaryOps::Add, var("a"), ExpressionKind::Number(1.0)), binary(BinaryOps::Mul, var("b"), ExpressionKind::Number(-2.0))));
+        // Expression tags aren't space separated, so `a -b` subtracts
+        assert_eq!(parse("{{a -b}}"), binary(BinaryOps::Sub, var("a"), var("b")));
+    }
+
+    #[test]
+    fn arithmetic_in_block_params() {
+        let params = |source: &str| match Parser::parse(source, ParserOptions::default()).unwrap().templates.remove(0).kind {
+            TemplateKind::Block(block) => block.params,
+            other => panic!("{:?}", other)
+        };
+        let var = |name: &str| ExpressionKind::Var(name.to_string());
+        let sub = |left, right| ExpressionKind::Binary(Box::new(BinaryOps::Sub(left, right)));
+        // A minus right before a parameter makes it negative, anywhere else it subtracts
+        assert_eq!(params("{{#range 1 -5 i}}{{/}}"), vec![ExpressionKind::Number(1.0), ExpressionKind::Number(-5.0), var("i")]);
+        assert_eq!(params("{{#range a -b i}}{{/}}"), vec![var("a"), ExpressionKind::Unary(Box::new(UnaryOps::Neg(var("b")))), var("i")]);
+        assert_eq!(params("{{#range a - b i}}{{/}}"), vec![sub(var("a"), var("b")), var("i")]);
+        assert_eq!(params("{{#range a-b i}}{{/}}"), vec![sub(var("a"), var("b")), var("i")]);
+        assert_eq!(params("{{#range f(a -b), (a -b) i}}{{/}}"), vec![
+            ExpressionKind::Call { var: Box::new(var("f")), params: vec![sub(var("a"), var("b"))] },
+            sub(var("a"), var("b")),
+            var("i")
+        ]);
+        // A slash before the end of the tag closes a block without a body
+        assert_eq!(params("{{#helper a / b /}}"), vec![ExpressionKind::Binary(Box::new(BinaryOps::Div(var("a"), var("b"))))]);
+        assert_eq!(params("{{#helper a/}}"), vec![var("a")]);
+    }
+
+}
@@ -0,0 +1,638 @@
+//! Templates are lowered to a flat list of instructions when they're added, that's what the
+//! renderer runs. Static text is kept in one string, helpers are resolved to slots in the
+//! [HelperTable], and the built-in `if` and `each` helpers become jumps. Branches of `if`
+//! blocks with a constant condition are jumped over. Variables bound by blocks are resolved
+//! to their slot in [Memory](crate::memory::Memory), see [Bindings].
+
+use std::cmp::Ordering;
+use std::ops::Range;
+use crate::ast::*;
+use crate::compiler::CompilerContext;
+use crate::error::{FinchError, FinchResult};
+use crate::helper::{Bindings, Builtin, HelperContext, HelperTable};
+use crate::optimize::constant;
+use crate::value::{RawValue, Arithmetic, arithmetic, compare_values};
+
+/// A helper block of a lowered template.
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub name: String,
+    /// The slot of the helper in the [HelperTable]
+    pub helper: usize,
+    pub params: Vec<Expression>,
+    pub hash: Vec<(String, Expression)>,
+    /// The instructions of the body
+    pub body: Option<Range<usize>>,
+    /// The position of the body in the template source
+    pub body_pos: Option<Range<usize>>,
+    /// The followup block
+    pub chain: Option<usize>,
+    /// The variables the helper binds, and their slots
+    pub bindings: Vec<(String, usize)>
+}
+
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    /// Static text, a range in [Program::text]
+    Text(Range<usize>),
+    Expression(Expression),
+    /// Calls the helper of a block, then continues at `next`, skipping the code of its body
+    Call { block: usize, next: usize },
+    /// Continues with the inlined code of a built-in helper if its slot still holds the built-in,
+    /// otherwise it calls the helper like [Instruction::Call]
+    Builtin { block: usize, builtin: Builtin, next: usize },
+    /// Jumps if the first parameter of the block is falsey
+    JumpIfFalsey { block: usize, target: usize },
+    Jump(usize),
+    /// Starts iterating over the first parameter of an `each` block, jumps to `end` if there's nothing to iterate
+    EachStart { block: usize, end: usize },
+    /// Moves on to the next item, jumps back to `start` if there is one
+    EachNext { block: usize, start: usize },
+    /// A tag which failed to parse
+    Error
+}
+
+// An `each` loop which is running, the items are read one at a time so arrays owned by the data source aren't copied
+struct Loop {
+    items: RawValue,
+    ind: usize,
+    len: usize,
+    // Where the loop variable is kept
+    slot: usize
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    pub blocks: Vec<Block>,
+    pub text: String
+}
+
+impl Program {
+
+    pub fn lower(template: &SubText, helpers: &mut HelperTable) -> Self {
+        let mut program = Self::default();
+        program.lower_subtext(template, &mut Scope { helpers, vars: vec![] });
+        program
+    }
+
+    fn lower_subtext(&mut self, text: &SubText, scope: &mut Scope) -> Range<usize> {
+        let start = self.instructions.len();
+        for temp in &text.templates {
+            match &temp.kind {
+                TemplateKind::Text(text) => {
+                    let text_start = self.text.len();
+                    self.text.push_str(text);
+                    self.instructions.push(Instruction::Text(text_start..self.text.len()));
+                },
+                TemplateKind::Expression(exp) => self.instructions.push(Instruction::Expression(scope.resolve(exp))),
+                TemplateKind::Block(block) => self.lower_block(block, scope),
+                TemplateKind::Error => self.instructions.push(Instruction::Error)
+            }
+        }
+        start..self.instructions.len()
+    }
+
+    fn lower_block(&mut self, block: &FnBlock, scope: &mut Scope) {
+        let start = self.instructions.len();
+        let id = self.declare_block(block, scope);
+        // Patched once the end is known
+        self.instructions.push(Instruction::Jump(0));
+        let inst = match block.name.as_str() {
+            "if" if can_inline_if(block) => {
+                self.lower_if(id, block, scope);
+                Instruction::Builtin { block: id, builtin: Builtin::If, next: self.instructions.len() }
+            },
+            "each" if can_inline_each(block) => {
+                let each_start = self.instructions.len();
+                self.instructions.push(Instruction::Jump(0));
+                let body = self.lower_body(id, block, scope);
+                self.instructions.push(Instruction::EachNext { block: id, start: body.start });
+                self.instructions[each_start] = Instruction::EachStart { block: id, end: self.instructions.len() };
+                Instruction::Builtin { block: id, builtin: Builtin::Each, next: self.instructions.len() }
+            },
+            _ => {
+                let mut link = Some((id, block));
+                while let Some((id, block)) = link {
+                    self.lower_body(id, block, scope);
+                    link = block.chain.as_deref().map(|chain| (self.blocks[id].chain.unwrap(), chain));
+                }
+                Instruction::Call { block: id, next: self.instructions.len() }
+            }
+        };
+        self.instructions[start] = inst;
+    }
+
+    // Adds the block and its chain, without their bodies
+    fn declare_block(&mut self, block: &FnBlock, scope: &mut Scope) -> usize {
+        let chain = block.chain.as_ref().map(|chain| self.declare_block(chain, scope));
+        let helper = scope.helpers.slot(&block.name);
+        let outer = scope.vars.len();
+        let bindings_of = scope.helpers.bindings(helper);
+        // The parameters which name variables are kept as they are
+        let (names, named) = match bindings_of {
+            Some(Bindings::Param(ind)) => (block.params.get(ind).and_then(var_name).into_iter().collect(), ind..ind + 1),
+            Some(Bindings::LastParam(ind)) => (bound_name(block, ind).into_iter().collect(), ind..block.params.len()),
+            _ => (vec![], 0..0)
+        };
+        let params = block.params.iter().enumerate().map(|(ind, param)| {
+            // Names aren't evaluated, so they're never bound variables
+            if named.contains(&ind) { Expression { ast: param.clone(), code: Code::lower(param, &[]) } } else { scope.resolve(param) }
+        }).collect();
+        let mut bindings = vec![];
+        // Like `where` of `each`, which is evaluated for every item
+        if let Some(Bindings::Param(_)) = bindings_of {
+            for name in &names {
+                scope.bind(&mut bindings, name);
+            }
+        }
+        let mut hash = Vec::with_capacity(block.hash.len());
+        for (key, value) in &block.hash {
+            hash.push((key.clone(), scope.resolve(value)));
+            if bindings_of == Some(Bindings::HashKeys) {
+                scope.bind(&mut bindings, key);
+            }
+        }
+        for name in &names {
+            scope.bind(&mut bindings, name);
+        }
+        scope.vars.truncate(outer);
+        self.blocks.push(Block {
+            name: block.name.clone(),
+            helper,
+            params,
+            hash,
+            body: None,
+            body_pos: block.block.as_ref().map(|body| body.pos.clone()),
+            chain,
+            bindings
+        });
+        self.blocks.len() - 1
+    }
+
+    fn lower_body(&mut self, id: usize, block: &FnBlock, scope: &mut Scope) -> Range<usize> {
+        let body = match &block.block {
+            Some(body) => {
+                let outer = scope.vars.len();
+                for (name, _) in &self.blocks[id].bindings {
+                    scope.vars.push(name.clone());
+                }
+                let body = self.lower_subtext(body, scope);
+                scope.vars.truncate(outer);
+                body
+            },
+            None => return self.instructions.len()..self.instructions.len()
+        };
+        self.blocks[id].body = Some(body.clone());
+        body
+    }
+
+    fn lower_if(&mut self, id: usize, block: &FnBlock, scope: &mut Scope) {
+        // The jumps to the end after each body
+        let mut jumps = vec![];
+        // Set after a branch which is always taken, the code of the branches after it only
+        // runs when the `if` helper was replaced
+        let mut taken = false;
+        let mut link = Some((id, block));
+        while let Some((id, block)) = link {
+            if taken || block.name == "else" {
+                self.lower_body(id, block, scope);
+            } else {
+                let condition = constant(&block.params[0]).map(|val| !val.is_falsey());
+                let check = self.instructions.len();
+                if condition != Some(true) {
+                    self.instructions.push(Instruction::Jump(0));
+                }
+                self.lower_body(id, block, scope);
+                jumps.push(self.instructions.len());
+                self.instructions.push(Instruction::Jump(0));
+                match condition {
+                    None => self.instructions[check] = Instruction::JumpIfFalsey { block: id, target: self.instructions.len() },
+                    Some(false) => self.instructions[check] = Instruction::Jump(self.instructions.len()),
+                    Some(true) => taken = true
+                }
+            }
+            link = block.chain.as_deref().map(|chain| (self.blocks[id].chain.unwrap(), chain));
+        }
+        let end = self.instructions.len();
+        for jump in jumps {
+            self.instructions[jump] = Instruction::Jump(end);
+        }
+    }
+
+    /// Runs the instructions in `range`, writing the result to `out`.
+    pub fn run<'a>(&'a self, range: Range<usize>, ctx: &mut CompilerContext<'a>, out: &mut String) -> FinchResult<()> {
+        self.run_flushing(range, ctx, out, None)
+    }
+
+    /// Like [run](Self::run), but whenever `out` has at least `flush` bytes after an instruction,
+    /// it's passed to [DataSource::write](crate::value::DataSource::write) and cleared.
+    pub fn run_flushing<'a>(&'a self, range: Range<usize>, ctx: &mut CompilerContext<'a>, out: &mut String, flush: Option<usize>) -> FinchResult<()> {
+        let mut loops = vec![];
+        let res = self.run_loops(range, ctx, out, flush, &mut loops);
+        // Leave the scopes of the loops which were interrupted by an error
+        for _ in 0..loops.len() {
+            ctx.cache.destroy();
+        }
+        res
+    }
+
+    fn run_loops<'a>(&'a self, range: Range<usize>, ctx: &mut CompilerContext<'a>, out: &mut String, flush: Option<usize>, loops: &mut Vec<Loop>) -> FinchResult<()> {
+        let mut pc = range.start;
+        while pc < range.end {
+            if let Some(size) = flush {
+                if !out.is_empty() && out.len() >= size {
+                    ctx.source.write(out)?;
+                    out.clear();
+                }
+            }
+            pc = match self.step(pc, ctx, out, loops) {
+                Ok(next) => next,
+                // The error can be caused by a value which isn't there yet, the rest of the
+                // template is still rendered to find everything else it's waiting for
+                Err(_) if ctx.source.pending() => self.skip(pc, ctx, loops),
+                Err(err) => return Err(err)
+            };
+        }
+        Ok(())
+    }
+
+    // Runs the instruction at `pc`, and returns the next one to run
+    fn step<'a>(&'a self, pc: usize, ctx: &mut CompilerContext<'a>, out: &mut String, loops: &mut Vec<Loop>) -> FinchResult<usize> {
+        match &self.instructions[pc] {
+            Instruction::Text(text) => out.push_str(&self.text[text.clone()]),
+            Instruction::Expression(exp) => match exp.eval(ctx)? {
+                RawValue::String(st) => out.push_str(&st),
+                val => out.push_str(&val.stringify(ctx.source)?)
+            },
+            Instruction::Call { block, next } => {
+                out.push_str(&self.blocks[*block].call(ctx)?);
+                return Ok(*next);
+            },
+            Instruction::Builtin { block, builtin, next } => {
+                if !ctx.compiler.helpers.is_builtin(self.blocks[*block].helper, *builtin) {
+                    out.push_str(&self.blocks[*block].call(ctx)?);
+                    return Ok(*next);
+                }
+            },
+            Instruction::JumpIfFalsey { block, target } => {
+                if self.blocks[*block].params[0].eval(ctx)?.is_falsey() {
+                    return Ok(*target);
+                }
+            },
+            Instruction::Jump(target) => return Ok(*target),
+            Instruction::EachStart { block, end } => {
+                let block = &self.blocks[*block];
+                let items = block.params[0].eval(ctx)?;
+                let len = items.array_len(ctx.source)?.ok_or(FinchError::InvalidArg(0))?;
+                if len == 0 {
+                    return Ok(*end);
+                }
+                let item = items.item(0, ctx.source)?;
+                ctx.cache.extend();
+                let slot = block.bindings[0].1;
+                ctx.cache.set(slot, item);
+                loops.push(Loop { items, ind: 0, len, slot });
+            },
+            Instruction::EachNext { start, .. } => {
+                let each = loops.last_mut().unwrap();
+                each.ind += 1;
+                if each.ind < each.len {
+                    let item = each.items.item(each.ind, ctx.source)?;
+                    ctx.cache.replace(each.slot, item);
+                    return Ok(*start);
+                }
+                loops.pop();
+                ctx.cache.destroy();
+            },
+            Instruction::Error => return Err(FinchError::Custom(String::from("Cannot render a template which contains syntax errors")))
+        }
+        Ok(pc + 1)
+    }
+
+    // Where to go on when the instruction at `pc` failed: past the block, the condition or the loop
+    fn skip(&self, pc: usize, ctx: &mut CompilerContext, loops: &mut Vec<Loop>) -> usize {
+        match &self.instructions[pc] {
+            Instruction::Call { next, .. } | Instruction::Builtin { next, .. } => *next,
+            Instruction::JumpIfFalsey { target, .. } => *target,
+            Instruction::EachStart { end, .. } => *end,
+            Instruction::EachNext { .. } => {
+                loops.pop();
+                ctx.cache.destroy();
+                pc + 1
+            },
+            _ => pc + 1
+        }
+    }
+
+}
+
+impl Block {
+
+    /// Calls the helper of the block and returns what it rendered.
+    pub fn call<'a>(&'a self, ctx: &mut CompilerContext<'a>) -> FinchResult<String> {
+        let compiler = ctx.compiler;
+        match compiler.helpers.get(self.helper) {
+            Some(helper) => helper.call(&mut HelperContext::new(self, ctx)),
+            None => Err(FinchError::HelperNotFound(self.name.clone()))
+        }
+    }
+
+}
+
+/// An expression of a lowered template. Variables bound by blocks are resolved to the slot
+/// they're kept in, the others are read from the data.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    /// The expression as it was written
+    pub ast: ExpressionKind,
+    code: Code
+}
+
+// What an expression evaluates, `key` is the whole path joined with dots, values read from
+// the data and from bound variables are remembered by it
+#[derive(Debug, Clone)]
+enum Code {
+    Literal(RawValue),
+    Data { path: Vec<String>, key: String },
+    /// The path is what's read from the variable, it's empty for the variable itself
+    Bound { slot: usize, path: Vec<String>, key: String },
+    Not(Box<Code>),
+    Neg(Box<Code>),
+    Binary(Operator, Box<(Code, Code)>),
+    Call(Box<Code>, Vec<Code>)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Compare,
+    Not,
+    StrictCompare,
+    StrictNot,
+    Gt,
+    Lt,
+    Gte,
+    Lte,
+    And,
+    Or,
+    Arithmetic(Arithmetic)
+}
+
+impl Expression {
+
+    pub fn eval(&self, ctx: &mut CompilerContext) -> FinchResult<RawValue> {
+        self.code.eval(ctx)
+    }
+
+}
+
+impl Code {
+
+    // `vars` are the bound variables by their slot
+    fn lower(exp: &ExpressionKind, vars: &[String]) -> Self {
+        match exp {
+            ExpressionKind::Var(name) => Self::variable(vec![name.clone()], vars),
+            ExpressionKind::VarDot(path) => Self::variable(path.clone(), vars),
+            ExpressionKind::Number(num) => Self::Literal(RawValue::Number(*num)),
+            ExpressionKind::BigInt(num) => Self::Literal(RawValue::BigInt(*num)),
+            ExpressionKind::String(val) => Self::Literal(RawValue::String(val.clone())),
+            ExpressionKind::Bool(val) => Self::Literal(RawValue::Boolean(*val)),
+            ExpressionKind::Undefined => Self::Literal(RawValue::Undefined),
+            ExpressionKind::Null => Self::Literal(RawValue::Null),
+            ExpressionKind::Unary(op) => match &**op {
+                UnaryOps::Not(exp) => Self::Not(Box::new(Self::lower(exp, vars))),
+                UnaryOps::Neg(exp) => Self::Neg(Box::new(Self::lower(exp, vars)))
+            },
+            ExpressionKind::Binary(op) => {
+                let operator = match &**op {
+                    BinaryOps::Compare(_, _) => Operator::Compare,
+                    BinaryOps::Not(_, _) => Operator::Not,
+                    BinaryOps::StrictCompare(_, _) => Operator::StrictCompare,
+                    BinaryOps::StrictNot(_, _) => Operator::StrictNot,
+                    BinaryOps::Gt(_, _) => Operator::Gt,
+                    BinaryOps::Lt(_, _) => Operator::Lt,
+                    BinaryOps::Gte(_, _) => Operator::Gte,
+                    BinaryOps::Lte(_, _) => Operator::Lte,
+                    BinaryOps::And(_, _) => Operator::And,
+                    BinaryOps::Or(_, _) => Operator::Or,
+                    BinaryOps::Add(_, _) => Operator::Arithmetic(Arithmetic::Add),
+                    BinaryOps::Sub(_, _) => Operator::Arithmetic(Arithmetic::Sub),
+                    BinaryOps::Mul(_, _) => Operator::Arithmetic(Arithmetic::Mul),
+                    BinaryOps::Div(_, _) => Operator::Arithmetic(Arithmetic::Div),
+                    BinaryOps::Rem(_, _) => Operator::Arithmetic(Arithmetic::Rem)
+                };
+                let (left, right) = op.operands();
+                Self::Binary(operator, Box::new((Self::lower(left, vars), Self::lower(right, vars))))
+            },
+            ExpressionKind::Call { var, params } => Self::Call(Box::new(Self::lower(var, vars)), params.iter().map(|param| Self::lower(param, vars)).collect())
+        }
+    }
+
+    fn variable(mut path: Vec<String>, vars: &[String]) -> Self {
+        let key = path.join(".");
+        // The innermost variable with the name
+        match vars.iter().rposition(|var| *var == path[0]) {
+            Some(slot) => {
+                path.remove(0);
+                Self::Bound { slot, path, key }
+            },
+            None => Self::Data { path, key }
+        }
+    }
+
+    fn eval(&self, ctx: &mut CompilerContext) -> FinchResult<RawValue> {
+        match self {
+            Self::Literal(val) => Ok(val.clone()),
+            Self::Data { path, key } => {
+                if let Some(thing) = ctx.cache.lookup(None, key) {
+                    return Ok(thing.clone());
+                }
+                let mut dat = ctx.data.get(&path[0], ctx.source)?;
+                for item in &path[1..] {
+                    dat = dat.get(item, ctx.source)?;
+                }
+                ctx.cache.remember(None, key.clone(), dat.clone());
+                Ok(dat)
+            },
+            Self::Bound { slot, path, .. } if path.is_empty() => Ok(ctx.cache.value(*slot)),
+            Self::Bound { slot, path, key } => {
+                if let Some(thing) = ctx.cache.lookup(Some(*slot), key) {
+                    return Ok(thing.clone());
+                }
+                let mut dat = ctx.cache.value(*slot);
+                for item in path {
+                    dat = dat.get(item, ctx.source)?;
+                }
+                ctx.cache.remember(Some(*slot), key.clone(), dat.clone());
+                Ok(dat)
+            },
+            Self::Not(exp) => Ok(RawValue::Boolean(exp.eval(ctx)?.is_falsey())),
+            Self::Neg(exp) => Ok(match exp.eval(ctx)? {
+                RawValue::BigInt(num) => RawValue::BigInt(-num),
+                val => RawValue::Number(-val.to_number())
+            }),
+            Self::Binary(operator, operands) => {
+                let (left, right) = &**operands;
+                let left = left.eval(ctx)?;
+                // The right side of && and || is only evaluated when it's needed
+                match operator {
+                    Operator::And if left.is_falsey() => return Ok(RawValue::Boolean(false)),
+                    Operator::Or if !left.is_falsey() => return Ok(left),
+                    _ => {}
+                }
+                let right = right.eval(ctx)?;
+                Ok(match operator {
+                    Operator::Compare => RawValue::Boolean(left.loose_equals(&right, ctx.source)?),
+                    Operator::Not => RawValue::Boolean(!left.loose_equals(&right, ctx.source)?),
+                    Operator::StrictCompare => RawValue::Boolean(left.strict_equals(&right, ctx.source)?),
+                    Operator::StrictNot => RawValue::Boolean(!left.strict_equals(&right, ctx.source)?),
+                    Operator::Gt => RawValue::Boolean(compare_values(&left, &right)? == Ordering::Greater),
+                    Operator::Lt => RawValue::Boolean(compare_values(&left, &right)? == Ordering::Less),
+                    Operator::Gte => RawValue::Boolean(compare_values(&left, &right)? != Ordering::Less),
+                    Operator::Lte => RawValue::Boolean(compare_values(&left, &right)? != Ordering::Greater),
+                    Operator::Arithmetic(op) => arithmetic(*op, &left, &right, ctx.source)?,
+                    Operator::And | Operator::Or if right.is_falsey() => RawValue::Boolean(false),
+                    Operator::And | Operator::Or => right
+                })
+            },
+            // The arguments are evaluated before the function
+            Self::Call(var, params) => {
+                let mut mapped_params = Vec::with_capacity(params.len());
+                for param in params {
+                    mapped_params.push(param.eval(ctx)?);
+                }
+                if let RawValue::Function(func) = var.eval(ctx)? {
+                    ctx.source.call(func, mapped_params)
+                } else {
+                    Err(FinchError::NotCallable)
+                }
+            }
+        }
+    }
+
+}
+
+// The variables bound by the blocks around what's being lowered, by their slot
+struct Scope<'a> {
+    helpers: &'a mut HelperTable,
+    vars: Vec<String>
+}
+
+impl Scope<'_> {
+
+    fn resolve(&self, exp: &ExpressionKind) -> Expression {
+        Expression { ast: exp.clone(), code: Code::lower(exp, &self.vars) }
+    }
+
+    // Adds a variable of a block, a variable with the same name of the same block keeps its slot
+    fn bind(&mut self, bindings: &mut Vec<(String, usize)>, name: &str) {
+        if bindings.iter().all(|(var, _)| var != name) {
+            bindings.push((name.to_string(), self.vars.len()));
+            self.vars.push(name.to_string());
+        }
+    }
+
+}
+
+fn var_name(exp: &ExpressionKind) -> Option<String> {
+    match exp {
+        ExpressionKind::Var(name) => Some(name.clone()),
+        _ => None
+    }
+}
+
+// The variable of a block whose last parameters are `name` or `as name`, starting at `ind`
+fn bound_name(block: &FnBlock, ind: usize) -> Option<String> {
+    match (block.params.get(ind), block.params.get(ind + 1)) {
+        (Some(ExpressionKind::Var(name)), None) => Some(name.clone()),
+        (Some(ExpressionKind::Var(keyword)), Some(ExpressionKind::Var(name))) if keyword == "as" && block.params.len() == ind + 2 => Some(name.clone()),
+        _ => None
+    }
+}
+
+// The built-in helpers are only inlined when they can't fail because of the shape of the block
+fn can_inline_if(block: &FnBlock) -> bool {
+    if block.params.is_empty() || block.block.is_none() {
+        return false;
+    }
+    match &block.chain {
+        None => true,
+        Some(chain) if chain.name == "if" => can_inline_if(chain),
+        Some(chain) => chain.name == "else" && chain.block.is_some() && chain.chain.is_none()
+    }
+}
+
+// Blocks with hash arguments or `reverse` filter or reorder the items, and the helper reports
+// unknown parameters and followups, so only the plain form is inlined
+fn can_inline_each(block: &FnBlock) -> bool {
+    block.params.len() == 2 && matches!(block.params[1], ExpressionKind::Var(_)) && block.block.is_some() && block.hash.is_empty() && block.chain.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Compiler, parser::ParserOptions};
+
+    // Expressions written out, bound variables by their slot
+    fn code(lowered: &Code) -> String {
+        match lowered {
+            Code::Literal(val) => format!("{:?}", val),
+            Code::Data { key, .. } => format!("data {}", key),
+            Code::Bound { slot, path, .. } => format!("slot {}{}", slot, path.iter().map(|key| format!(".{}", key)).collect::<String>()),
+            Code::Not(val) => format!("!{}", code(val)),
+            Code::Neg(val) => format!("-{}", code(val)),
+            Code::Binary(op, operands) => format!("({} {:?} {})", code(&operands.0), op, code(&operands.1)),
+            Code::Call(func, args) => format!("{}({})", code(func), args.iter().map(code).collect::<Vec<String>>().join(", "))
+        }
+    }
+
+    // The instructions of a template, written out so they're easy to compare
+    fn lowered(template: &str) -> (Vec<String>, String) {
+        let mut compiler = Compiler::new();
+        compiler.add_template("test", template, ParserOptions::default()).unwrap();
+        let program = &compiler.templates["test"].program;
+        let instructions = program.instructions.iter().map(|instruction| match instruction {
+            Instruction::Text(range) => format!("text {:?}", range),
+            Instruction::Expression(exp) => format!("write {}", code(&exp.code)),
+            Instruction::Call { block, next } => format!("call {} {} next {}", block, program.blocks[*block].name, next),
+            Instruction::Builtin { block, builtin, next } => format!("builtin {} {:?} next {}", block, builtin, next),
+            Instruction::JumpIfFalsey { block, target } => format!("unless {} jump {}", code(&program.blocks[*block].params[0].code), target),
+            Instruction::Jump(target) => format!("jump {}", target),
+            Instruction::EachStart { block, end } => format!("each {} end {}", code(&program.blocks[*block].params[0].code), end),
+            Instruction::EachNext { start, .. } => format!("next start {}", start),
+            Instruction::Error => String::from("error")
+        }).collect();
+        (instructions, program.text.clone())
+    }
+
+    #[test]
+    fn if_and_each_become_jumps() {
+        let (instructions, text) = lowered("a{{x}}{{#if c}}yes{{/#else}}no{{/}}{{#each xs i}}{{i.n}}{{/}}");
+        assert_eq!(text, "ayesno");
+        assert_eq!(instructions, [
+            "text 0..1", "write data x",
+            "builtin 1 If next 7", "unless data c jump 6", "text 1..4", "jump 7", "text 4..6",
+            "builtin 2 Each next 11", "each data xs end 11", "write slot 0.n", "next start 9"
+        ]);
+    }
+
+    #[test]
+    fn other_blocks_are_calls() {
+        // each with more than an array and a name is left to the helper, and bound variables are slots
+        let (instructions, _) = lowered("{{#each xs i reverse}}{{i}}{{/}}{{#shout x.y /}}{{#let v = 1}}{{v + w}}{{/}}");
+        assert_eq!(instructions, ["call 0 each next 2", "write slot 0", "call 1 shout next 3", "call 2 let next 5", "write (slot 0 Arithmetic(Add) data w)"]);
+        let (instructions, _) = lowered("{{#each xs i}}{{/#else}}{{/}}{{#each xs i where=(i)}}{{/}}");
+        assert_eq!(instructions, ["call 1 each next 1", "call 2 each next 2"]);
+    }
+
+    #[test]
+    fn constant_branches_are_jumped_over() {
+        let (instructions, text) = lowered("{{#if true}}T{{/#else}}F{{/}}{{#if false}}A{{/#if d}}B{{/}}");
+        assert_eq!(text, "TFAB");
+        // The bodies after a branch which is always taken only run when `if` is replaced
+        assert_eq!(instructions, [
+            "builtin 1 If next 4", "text 0..1", "jump 4", "text 1..2",
+            "builtin 3 If next 11", "jump 8", "text 2..3", "jump 11", "unless data d jump 11", "text 3..4", "jump 11"
+        ]);
+    }
+}
@@ -0,0 +1,465 @@
+// The runtime of templates compiled to javascript. It behaves exactly like the rust renderer,
+// so a template renders to the same string with either of them.
+
+const hasOwn = (obj, key) => Object.prototype.hasOwnProperty.call(obj, key);
+
+const fail = (message) => { throw new Error(message); };
+
+// Values are written like String(value) does, except that objects are only converted
+// with their own methods when the stringify option is set
+function str(c, val, depth = 0) {
+    switch (typeof val) {
+    case "string": return val;
+    case "number": case "bigint": case "boolean": return String(val);
+    case "undefined": case "symbol": return "undefined";
+    }
+    if (val === null) return "null";
+    if (Array.isArray(val)) {
+        if (depth > 64) fail("The array is nested too deeply to print, it might be circular");
+        return val.map((item) => item === null || item === undefined ? "" : str(c, item, depth + 1)).join(",");
+    }
+    const mode = c.options.stringify;
+    if (mode === "toJSON" && typeof val.toJSON === "function") return String(val.toJSON());
+    if (mode) return String(val);
+    return typeof val === "function" ? Function.prototype.toString.call(val) : "[object Object]";
+}
+
+const falsy = (val) => !val;
+
+function toNumber(val) {
+    switch (typeof val) {
+    case "number": return val;
+    case "bigint": return Number(val);
+    case "boolean": return val ? 1 : 0;
+    case "string": return Number(val);
+    }
+    return val === null ? 0 : NaN;
+}
+
+function neg(val) {
+    return typeof val === "bigint" ? -val : -toNumber(val);
+}
+
+const isObject = (val) => (typeof val === "object" && val !== null) || typeof val === "function";
+
+// BigInts are kept in 128 bit integers by the rust renderer
+const BIGINT_MAX = (1n << 127n) - 1n;
+
+function add(c, left, right) {
+    if (typeof left === "string" || typeof right === "string" || isObject(left) || isObject(right)) return str(c, left) + str(c, right);
+    return arith(c, "+", left, right);
+}
+
+function arith(c, op, left, right) {
+    // Objects become numbers through their text, so [5] * 2 is 10
+    if (isObject(left)) left = toNumber(str(c, left));
+    if (isObject(right)) right = toNumber(str(c, right));
+    if (typeof left === "bigint" || typeof right === "bigint") {
+        if (typeof left !== typeof right) fail("Cannot mix BigInt and other types, use explicit conversions");
+        if ((op === "/" || op === "%") && right === 0n) fail("Division by zero");
+        const res = op === "+" ? left + right : op === "-" ? left - right : op === "*" ? left * right : op === "/" ? left / right : left % right;
+        if (res > BIGINT_MAX || res < -BIGINT_MAX - 1n) fail("The result doesn't fit in a 128-bit signed integer (i128)");
+        return res;
+    }
+    [left, right] = [toNumber(left), toNumber(right)];
+    return op === "+" ? left + right : op === "-" ? left - right : op === "*" ? left * right : op === "/" ? left / right : left % right;
+}
+
+// Like javascript's ==, except that objects are never equal to values which aren't objects
+function eq(left, right) {
+    const nullish = (val) => val === null || val === undefined;
+    if (nullish(left) || nullish(right)) return nullish(left) && nullish(right);
+    const [lt, rt] = [typeof left, typeof right];
+    if (lt === "boolean" && rt !== "boolean") return eq(left ? 1 : 0, right);
+    if (rt === "boolean" && lt !== "boolean") return eq(left, right ? 1 : 0);
+    if (lt === "number" && rt === "string") return left === toNumber(right);
+    if (lt === "string" && rt === "number") return toNumber(left) === right;
+    // Strings are read like BigInt(string), which is what == does
+    if ((lt === "bigint" && rt === "string") || (lt === "string" && rt === "bigint")) return left == right;
+    if (lt === "bigint" && rt === "number") return Number.isInteger(right) && BigInt(right) === left;
+    if (lt === "number" && rt === "bigint") return Number.isInteger(left) && BigInt(left) === right;
+    return left === right;
+}
+
+function deepEq(left, right, depth) {
+    if (depth > 64) fail("The values are nested too deeply to compare, they might be circular");
+    if (left === right) return true;
+    if (Array.isArray(left) && Array.isArray(right)) {
+        return left.length === right.length && left.every((item, ind) => deepEq(item, right[ind], depth + 1));
+    }
+    const isObject = (val) => typeof val === "object" && val !== null && !Array.isArray(val);
+    if (!isObject(left) || !isObject(right)) return false;
+    const [keys, otherKeys] = [Object.keys(left).sort(cmpStr), Object.keys(right).sort(cmpStr)];
+    if (keys.length !== otherKeys.length || keys.some((key, ind) => key !== otherKeys[ind])) return false;
+    return keys.every((key) => deepEq(left[key], right[key], depth + 1));
+}
+
+function cmp(left, right) {
+    if (typeof left === "string" && typeof right === "string") return cmpStr(left, right);
+    const isNum = (val) => typeof val === "number" || typeof val === "bigint";
+    if (!isNum(left) || !isNum(right)) fail("Cannot use >, <, >=, <= on values which aren't both numbers or both strings");
+    if (typeof left !== "bigint" || typeof right !== "bigint") {
+        left = Number(left);
+        right = Number(right);
+    }
+    return left > right ? 1 : left === right ? 0 : -1;
+}
+
+function or(left, right) {
+    if (!falsy(left)) return left;
+    const val = right();
+    return falsy(val) ? false : val;
+}
+
+function and(left, right) {
+    if (falsy(left)) return false;
+    const val = right();
+    return falsy(val) ? false : val;
+}
+
+function get(obj, key) {
+    if (Array.isArray(obj)) {
+        if (key === "length") return obj.length;
+        return /^\d+$/.test(key) ? obj[Number(key)] : undefined;
+    }
+    if ((typeof obj === "object" && obj !== null) || typeof obj === "function") return obj[key];
+    return fail("Expected type object for dot notation.");
+}
+
+// Variables set by helpers shadow the data
+const v = (c, sc, name) => name in sc ? sc[name] : get(c.data, name);
+
+function path(c, sc, names) {
+    let val = v(c, sc, names[0]);
+    for (let ind = 1; ind < names.length; ind++) val = get(val, names[ind]);
+    return val;
+}
+
+function fn(args, func) {
+    if (typeof func !== "function") fail("Property is not callable.");
+    try {
+        return func(...args);
+    } catch {
+        return fail("An error occured in a JS function");
+    }
+}
+
+function param(c, sc, block, ind) {
+    const param = block.params[ind];
+    return param ? param.run(c, sc) : fail(`Argument ${ind} is invalid`);
+}
+
+function body(c, sc, block) {
+    return block.body ? block.body(c, sc) : fail(`Expected body for ${block.name} helper`);
+}
+
+// The name of the variable a block binds, the last parameter, either `name` or `as name`
+function boundName(block, ind) {
+    const [first, second] = block.params.slice(ind);
+    if (first && first.var !== undefined && block.params.length === ind + 1) return first.var;
+    if (first && first.var === "as" && second && second.var !== undefined && block.params.length === ind + 2) return second.var;
+    return fail(`Argument ${ind} is invalid`);
+}
+
+function number(val, ind) {
+    if (typeof val === "bigint") return Number(val);
+    return typeof val === "number" && isFinite(val) ? val : fail(`Argument ${ind} is invalid`);
+}
+
+function countLoop(c, sc, block, name, count, value) {
+    if (!block.body) fail(`Expected body for ${block.name} helper`);
+    const scope = Object.create(sc);
+    let res = "";
+    for (let ind = 0; ind < count; ind++) {
+        scope[name] = value(ind);
+        res += block.body(c, scope);
+    }
+    return res;
+}
+
+// range and times fail rather than hang on huge counts, like MAX_ITERATIONS in default_helpers.rs
+const MAX_ITERATIONS = 1000000;
+
+function boundedLoop(c, sc, block, name, count, value) {
+    if (!block.body) fail(`Expected body for ${block.name} helper`);
+    if (count > MAX_ITERATIONS) fail(`${block.name} can't render its body more than ${MAX_ITERATIONS} times`);
+    return countLoop(c, sc, block, name, count, value);
+}
+
+// A range starting with a BigInt counts in BigInts, a number end is rounded towards the start
+function bigintRange(c, sc, block, from, end) {
+    const clamp = (val) => val > BIGINT_MAX ? BIGINT_MAX : val < -BIGINT_MAX - 1n ? -BIGINT_MAX - 1n : val;
+    let to = end;
+    if (typeof end !== "bigint") {
+        to = clamp(BigInt(Math.floor(end)));
+        if (to < from) to = clamp(BigInt(Math.ceil(end)));
+    }
+    const stepEntry = block.hash.find(([key]) => key === "step");
+    const stepValue = stepEntry ? stepEntry[1](c, sc) : 1n;
+    if (typeof stepValue !== "bigint" && (typeof stepValue !== "number" || !Number.isInteger(stepValue))) fail("Hash argument step is invalid");
+    const step = BigInt(stepValue);
+    if (step <= 0n) fail("The step of range has to be a positive number");
+    const name = boundName(block, 2);
+    const dist = to < from ? from - to : to - from;
+    const count = dist / step >= BigInt(MAX_ITERATIONS) ? MAX_ITERATIONS + 1 : Number(dist / step) + 1;
+    const dir = to < from ? -1n : 1n;
+    return boundedLoop(c, sc, block, name, count, (ind) => from + dir * BigInt(ind) * step);
+}
+
+const keyOf = (item, path) => path.split(".").reduce(get, item);
+
+function cmpStr(left, right) {
+    // By code points, like rust compares strings
+    for (let ind = 0; ind < left.length && ind < right.length;) {
+        const x = left.codePointAt(ind), y = right.codePointAt(ind);
+        if (x !== y) return x < y ? -1 : 1;
+        ind += x > 0xffff ? 2 : 1;
+    }
+    return left.length === right.length ? 0 : left.length < right.length ? -1 : 1;
+}
+
+// Numbers come first, then strings, booleans, and everything else. NaN is sorted after the other numbers
+function order(left, right) {
+    const rank = (val) => {
+        switch (typeof val) {
+        case "number": case "bigint": return 0;
+        case "string": return 1;
+        case "boolean": return 2;
+        }
+        return 3;
+    };
+    const [rl, rr] = [rank(left), rank(right)];
+    if (rl !== rr) return rl < rr ? -1 : 1;
+    if (rl === 1) return cmpStr(left, right);
+    if (rl === 2) return left === right ? 0 : left ? 1 : -1;
+    if (rl === 3) return 0;
+    if (typeof left === "bigint" && typeof right === "bigint") return left < right ? -1 : left > right ? 1 : 0;
+    const [x, y] = [Number(left), Number(right)];
+    if (x !== x || y !== y) return (x !== x) - (y !== y);
+    return x < y ? -1 : x > y ? 1 : 0;
+}
+
+function hashCount(c, sc, block, key) {
+    const entry = block.hash.find(([name]) => name === key);
+    if (!entry) return undefined;
+    const val = entry[1](c, sc);
+    if (typeof val === "bigint") return val > 0n ? Number(val) : 0;
+    if (typeof val !== "number" || !isFinite(val)) fail(`The ${key} argument of each has to be a number`);
+    return Math.floor(Math.max(val, 0));
+}
+
+// The items each renders when it has hash arguments, see the rust helper
+function select(c, scope, block, items, name, reversed) {
+    const unknown = block.hash.find(([key]) => !["where", "sort", "reverse", "offset", "limit"].includes(key));
+    if (unknown) fail(`each has no ${unknown[0]} argument`);
+    const hash = (key) => block.hash.find(([name]) => name === key);
+    const filter = hash("where");
+    let selected = [];
+    for (const item of items) {
+        if (filter) {
+            scope[name] = item;
+            if (falsy(filter[1](c, scope))) continue;
+        }
+        selected.push(item);
+    }
+    const sort = hash("sort");
+    if (sort) {
+        const path = sort[1](c, scope);
+        if (typeof path !== "string") fail("The sort argument of each has to be a string");
+        selected = selected.map((item) => [keyOf(item, path), item]).sort((left, right) => order(left[0], right[0])).map((entry) => entry[1]);
+    }
+    const reverse = hash("reverse");
+    if (reversed || (reverse && !falsy(reverse[1](c, scope)))) selected.reverse();
+    const offset = hashCount(c, scope, block, "offset");
+    if (offset !== undefined) selected = selected.slice(offset);
+    const limit = hashCount(c, scope, block, "limit");
+    if (limit !== undefined) selected = selected.slice(0, limit);
+    return selected;
+}
+
+// The and / or helpers, the conditions after the one which decides the result aren't evaluated
+function allOrAny(c, sc, block, name, all) {
+    if (!block.params.length) fail("Argument 0 is invalid");
+    if (!block.body) fail(`Expected body for ${name} helper`);
+    let matched = all;
+    for (let ind = 0; ind < block.params.length; ind++) {
+        if (falsy(param(c, sc, block, ind)) === all) {
+            matched = !all;
+            break;
+        }
+    }
+    if (matched) return block.body(c, sc);
+    const chain = block.chain;
+    if (!chain) return "";
+    if (chain.name === "else") return chain.body ? chain.body(c, sc) : fail("Expected body for else helper");
+    return fail(`Expected an else follow up block, found ${chain.name}`);
+}
+
+const helpers = {
+    each(c, sc, block) {
+        if (block.chain) fail(`each has no ${block.chain.name} follow up block`);
+        const name = block.params[1] && block.params[1].var;
+        if (name === undefined) fail("Argument 1 is invalid");
+        // The only other parameter is a bare `reverse`
+        const reverse = block.params.length > 2;
+        if (reverse && block.params[2].var !== "reverse") fail("Argument 2 is invalid");
+        if (block.params.length > 3) fail("Argument 3 is invalid");
+        const items = param(c, sc, block, 0);
+        if (!Array.isArray(items)) fail("Argument 0 is invalid");
+        const scope = Object.create(sc);
+        let res = "";
+        const selected = block.hash.length ? select(c, scope, block, items, name, reverse) : reverse ? items.slice().reverse() : items;
+        for (const item of selected) {
+            scope[name] = item;
+            res += body(c, scope, block);
+        }
+        return res;
+    },
+    groupBy(c, sc, block) {
+        const items = param(c, sc, block, 0);
+        if (!Array.isArray(items)) fail("Argument 0 is invalid");
+        const path = param(c, sc, block, 1);
+        if (typeof path !== "string") fail("Argument 1 is invalid");
+        const name = boundName(block, 2);
+        if (!block.body) fail("Expected body for groupBy helper");
+        const groups = [];
+        for (const item of items) {
+            const key = keyOf(item, path);
+            const group = groups.find((group) => eq(group.key, key));
+            if (group) group.items.push(item);
+            else groups.push({ key, items: [item] });
+        }
+        return countLoop(c, sc, block, name, groups.length, (ind) => groups[ind]);
+    },
+    template(c, sc, block) {
+        if (block.body) fail("The template helper cannot have a body");
+        const name = block.params[0] && block.params[0].str;
+        if (name === undefined) fail("Argument 0 is invalid");
+        const data = param(c, sc, block, 1);
+        if (typeof data !== "object" || data === null || Array.isArray(data)) fail("Argument 1 is invalid");
+        if (!hasOwn(c.templates, name)) fail(`The template ${name} doesn't exist`);
+        return c.templates[name](data, c.options);
+    },
+    js(c, sc, block) {
+        if (block.source === null) fail("Expected body for js helper");
+        return str(c, new Function("data", block.source)(c.data));
+    },
+    let(c, sc, block) {
+        if (block.params.length) fail("Argument 0 is invalid");
+        if (!block.body) fail("Expected body for let helper");
+        const scope = Object.create(sc);
+        for (const [key, value] of block.hash) scope[key] = value(c, scope);
+        return block.body(c, scope);
+    },
+    with(c, sc, block) {
+        const val = param(c, sc, block, 0);
+        const name = boundName(block, 1);
+        if (!block.body) fail("Expected body for with helper");
+        if (!falsy(val)) {
+            const scope = Object.create(sc);
+            scope[name] = val;
+            return block.body(c, scope);
+        }
+        const chain = block.chain;
+        if (!chain) return "";
+        if (chain.name === "else") return chain.body ? chain.body(c, sc) : fail("Expected body for else helper");
+        return fail(`Expected an else follow up block, found ${chain.name}`);
+    },
+    unless(c, sc, block) {
+        const val = param(c, sc, block, 0);
+        if (!block.body) fail("Expected body for unless helper");
+        if (falsy(val)) return block.body(c, sc);
+        const chain = block.chain;
+        if (!chain) return "";
+        if (chain.name === "else") return chain.body ? chain.body(c, sc) : fail("Expected body for else helper");
+        return fail(`Expected an else follow up block, found ${chain.name}`);
+    },
+    deepEqual(c, sc, block) {
+        const left = param(c, sc, block, 0), right = param(c, sc, block, 1);
+        if (!block.body) fail("Expected body for deepEqual helper");
+        if (deepEq(left, right, 0)) return block.body(c, sc);
+        const chain = block.chain;
+        if (!chain) return "";
+        if (chain.name === "else") return chain.body ? chain.body(c, sc) : fail("Expected body for else helper");
+        return fail(`Expected an else follow up block, found ${chain.name}`);
+    },
+    and(c, sc, block) {
+        return allOrAny(c, sc, block, "and", true);
+    },
+    or(c, sc, block) {
+        return allOrAny(c, sc, block, "or", false);
+    },
+    switch(c, sc, block) {
+        const val = param(c, sc, block, 0);
+        let fallback = null;
+        for (let link = block.chain; link; link = link.chain) {
+            if (link.name === "case") {
+                if (!link.params.length) fail("Argument 0 is invalid");
+                for (const param of link.params) {
+                    if (val === param.run(c, sc)) return body(c, sc, link);
+                }
+            } else if (link.name === "default" && !fallback) {
+                fallback = link;
+            } else if (link.name === "default") {
+                fail("A switch can only have one default follow up block");
+            } else {
+                fail(`Expected case / default follow up blocks, found ${link.name}`);
+            }
+        }
+        return fallback ? body(c, sc, fallback) : "";
+    },
+    range(c, sc, block) {
+        const start = param(c, sc, block, 0);
+        const end = param(c, sc, block, 1);
+        const from = number(start, 0), to = number(end, 1);
+        if (typeof start === "bigint") return bigintRange(c, sc, block, start, end);
+        const stepEntry = block.hash.find(([key]) => key === "step");
+        const stepValue = stepEntry ? stepEntry[1](c, sc) : 1;
+        if (typeof stepValue !== "bigint" && (typeof stepValue !== "number" || !isFinite(stepValue))) fail("Hash argument step is invalid");
+        const step = Number(stepValue);
+        if (step <= 0) fail("The step of range has to be a positive number");
+        const name = boundName(block, 2);
+        const count = Math.floor(Math.abs(to - from) / step + 1e-9) + 1;
+        const dir = to < from ? -1 : 1;
+        return boundedLoop(c, sc, block, name, count, (ind) => from + dir * ind * step);
+    },
+    times(c, sc, block) {
+        const times = param(c, sc, block, 0);
+        const count = Math.floor(Math.max(number(times, 0), 0));
+        const name = boundName(block, 1);
+        const bigint = typeof times === "bigint";
+        return boundedLoop(c, sc, block, name, count, (ind) => bigint ? BigInt(ind) : ind);
+    },
+    if(c, sc, block) {
+        const val = param(c, sc, block, 0);
+        if (!block.body) fail("Expected body for if helper");
+        if (!falsy(val)) return block.body(c, sc);
+        const chain = block.chain;
+        if (!chain) return "";
+        if (chain.name === "if") return run(c, sc, chain);
+        if (chain.name === "else") return chain.body ? chain.body(c, sc) : fail("Expected body for else helper");
+        return fail(`Expected if / else follow up blocks, found ${chain.name}`);
+    }
+};
+
+function run(c, sc, block) {
+    if (hasOwn(c.helpers, block.name)) {
+        const args = block.params.map((param) => param.run(c, sc));
+        const hash = {};
+        for (const [key, value] of block.hash) hash[key] = value(c, sc);
+        const res = c.helpers[block.name](args, block.body ? block.body(c, sc) : undefined, hash);
+        return str(c, res);
+    }
+    if (hasOwn(helpers, block.name)) return helpers[block.name](c, sc, block);
+    return fail(`Couldn't find helper "${block.name}"`);
+}
+
+function render(root, data, options) {
+    options = options || {};
+    if (options.stringify !== undefined && options.stringify !== "toString" && options.stringify !== "toJSON") {
+        fail("The stringify option must be \"toString\" or \"toJSON\"");
+    }
+    return root({ data, options, helpers: options.helpers || {}, templates: options.templates || {} }, Object.create(null));
+}
+
+return { str, falsy, neg, add, arith, eq, cmp, or, and, v, path, fn, run, render };
@@ -0,0 +1,267 @@
+//! Compiles templates to standalone javascript modules, which render templates without the
+//! native addon. The generated code includes a small runtime which works exactly like the
+//! rust renderer, so both render a template to the same string.
+
+use crate::ast::*;
+use crate::error::FinchResult;
+use crate::parser::{Parser, ParserOptions};
+
+static RUNTIME: &str = include_str!("runtime.js");
+
+#[derive(Clone, Copy, Default)]
+pub struct TranspileOptions {
+    pub parser: ParserOptions,
+    /// Export the render function with `module.exports` instead of `export default`
+    pub commonjs: bool
+}
+
+/// Parses a template and compiles it to a javascript module.
+pub fn transpile(source: &str, options: TranspileOptions) -> FinchResult<String> {
+    let ast = Parser::parse(source, options.parser)?;
+    Ok(Transpiler::new(options).transpile(&ast, source))
+}
+
+pub struct Transpiler {
+    pub options: TranspileOptions
+}
+
+pub struct TranspilerContext<'a> {
+    pub transpiler: &'a Transpiler,
+    pub original: &'a str,
+    // The declarations of all blocks, a block is always declared after the blocks it contains
+    blocks: Vec<String>
+}
+
+impl Transpiler {
+
+    pub fn new(options: TranspileOptions) -> Self {
+        Self { options }
+    }
+
+    /// Compiles a parsed template, `original` is the source it was parsed from.
+    ///
+    /// The module exports a function which takes the data and an optional object with the
+    /// `helpers` and the compiled `templates` the template can use:
+    ///
+    /// ```js
+    /// render({ name: "Google" }, { helpers: { upper: (args) => args[0].toUpperCase() }, templates: { user: renderUser } });
+    /// ```
+    pub fn transpile(&self, template: &SubText, original: &str) -> String {
+        let mut ctx = TranspilerContext {
+            transpiler: self,
+            original,
+            blocks: vec![]
+        };
+        let root = template.transpile(&mut ctx);
+        let mut res = String::from("// Compiled from a finch template\n\"use strict\";\n\nconst rt = (() => {\n");
+        res.push_str(RUNTIME);
+        res.push_str("})();\n\n");
+        for block in &ctx.blocks {
+            res.push_str(block);
+        }
+        res.push_str(&format!("const root = {};\n\n", root));
+        res.push_str("function render(data, options) {\n    return rt.render(root, data, options);\n}\n\n");
+        res.push_str(if self.options.commonjs { "module.exports = render;\n" } else { "export default render;\n" });
+        res
+    }
+
+}
+
+pub trait Transpilable {
+    fn transpile(&self, ctx: &mut TranspilerContext) -> String;
+}
+
+fn string_literal(val: &str) -> String {
+    serde_json::to_string(val).unwrap()
+}
+
+impl Transpilable for SubText {
+
+    // An arrow function which takes the render state and the current scope, and returns the rendered text
+    fn transpile(&self, ctx: &mut TranspilerContext) -> String {
+        let mut res = String::from("(c, sc) => {\n    let o = \"\";\n");
+        for temp in &self.templates {
+            match &temp.kind {
+                TemplateKind::Text(text) => res.push_str(&format!("    o += {};\n", string_literal(text))),
+                TemplateKind::Expression(exp) => res.push_str(&format!("    o += rt.str(c, {});\n", exp.transpile(ctx))),
+                TemplateKind::Block(block) => res.push_str(&format!("    o += rt.run(c, sc, {});\n", block.transpile(ctx))),
+                TemplateKind::Error => res.push_str("    throw new Error(\"Cannot render a template which contains syntax errors\");\n")
+            }
+        }
+        res.push_str("    return o;\n}");
+        res
+    }
+
+}
+
+impl Transpilable for FnBlock {
+
+    // Declares the block and returns its name
+    fn transpile(&self, ctx: &mut TranspilerContext) -> String {
+        let chain = match &self.chain {
+            Some(chain) => chain.transpile(ctx),
+            None => String::from("null")
+        };
+        let params = self.params.iter().map(|param| {
+            let mut res = format!("{{ run: (c, sc) => {}", param.transpile(ctx));
+            // Some helpers need the expression itself, not its value
+            match param {
+                ExpressionKind::Var(name) => res.push_str(&format!(", var: {}", string_literal(name))),
+                ExpressionKind::String(val) => res.push_str(&format!(", str: {}", string_literal(val))),
+                _ => {}
+            }
+            res.push_str(" }");
+            res
+        }).collect::<Vec<String>>();
+        let hash = self.hash.iter()
+            .map(|(key, value)| format!("[{}, (c, sc) => {}]", string_literal(key), value.transpile(ctx)))
+            .collect::<Vec<String>>();
+        let body = match &self.block {
+            Some(body) => body.transpile(ctx),
+            None => String::from("null")
+        };
+        let source = match &self.block {
+            Some(body) if self.name == "js" => string_literal(&ctx.original[body.pos.clone()]),
+            _ => String::from("null")
+        };
+        let name = format!("b{}", ctx.blocks.len());
+        ctx.blocks.push(format!(
+            "const {} = {{\n    name: {},\n    params: [{}],\n    hash: [{}],\n    body: {},\n    source: {},\n    chain: {}\n}};\n\n",
+            name, string_literal(&self.name), params.join(", "), hash.join(", "), body, source, chain
+        ));
+        name
+    }
+
+}
+
+impl Transpilable for ExpressionKind {
+
+    #[allow(clippy::only_used_in_recursion)]
+    fn transpile(&self, ctx: &mut TranspilerContext) -> String {
+        match self {
+            ExpressionKind::String(val) => string_literal(val),
+            ExpressionKind::Number(num) => {
+                if num.is_nan() { String::from("NaN") }
+                else if num.is_infinite() { String::from(if *num > 0.0 { "Infinity" } else { "(-Infinity)" }) }
+                else if num.is_sign_negative() { format!("({})", num) }
+                else { num.to_string() }
+            },
+            ExpressionKind::BigInt(num) => if *num < 0 { format!("({}n)", num) } else { format!("{}n", num) },
+            ExpressionKind::Bool(val) => val.to_string(),
+            ExpressionKind::Undefined => String::from("undefined"),
+            ExpressionKind::Null => String::from("null"),
+            ExpressionKind::Var(name) => format!("rt.v(c, sc, {})", string_literal(name)),
+            ExpressionKind::VarDot(path) => format!("rt.path(c, sc, [{}])", path.iter().map(|name| string_literal(name)).collect::<Vec<String>>().join(", ")),
+            ExpressionKind::Unary(exp) => match &**exp {
+                UnaryOps::Not(exp) => format!("rt.falsy({})", exp.transpile(ctx)),
+                UnaryOps::Neg(exp) => format!("rt.neg({})", exp.transpile(ctx))
+            },
+            ExpressionKind::Binary(exp) => {
+                let (left, right) = exp.operands();
+                let (left, right) = (left.transpile(ctx), right.transpile(ctx));
+                match &**exp {
+                    BinaryOps::Compare(_, _) => format!("rt.eq({}, {})", left, right),
+                    BinaryOps::Not(_, _) => format!("!rt.eq({}, {})", left, right),
+                    BinaryOps::StrictCompare(_, _) => format!("({} === {})", left, right),
+                    BinaryOps::StrictNot(_, _) => format!("({} !== {})", left, right),
+                    BinaryOps::Gt(_, _) => format!("(rt.cmp({}, {}) === 1)", left, right),
+                    BinaryOps::Lt(_, _) => format!("(rt.cmp({}, {}) === -1)", left, right),
+                    BinaryOps::Gte(_, _) => format!("(rt.cmp({}, {}) !== -1)", left, right),
+                    BinaryOps::Lte(_, _) => format!("(rt.cmp({}, {}) !== 1)", left, right),
+                    BinaryOps::And(_, _) => format!("rt.and({}, () => {})", left, right),
+                    BinaryOps::Or(_, _) => format!("rt.or({}, () => {})", left, right),
+                    BinaryOps::Add(_, _) => format!("rt.add(c, {}, {})", left, right),
+                    BinaryOps::Sub(_, _) => format!("rt.arith(c, \"-\", {}, {})", left, right),
+                    BinaryOps::Mul(_, _) => format!("rt.arith(c, \"*\", {}, {})", left, right),
+                    BinaryOps::Div(_, _) => format!("rt.arith(c, \"/\", {}, {})", left, right),
+                    BinaryOps::Rem(_, _) => format!("rt.arith(c, \"%\", {}, {})", left, right)
+                }
+            },
+            // The arguments are evaluated before the function, like in the rust renderer
+            ExpressionKind::Call { var, params } => format!("rt.fn([{}], {})", params.iter().map(|param| param.transpile(ctx)).collect::<Vec<String>>().join(", "), var.transpile(ctx))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compiler;
+    use serde_json::{json, Value};
+
+    // Templates which the compiled javascript has to render exactly like the rust renderer
+    fn fixtures() -> Vec<(&'static str, Value)> {
+        vec![
+            ("{{a}} {{b}} {{c}} {{d}} {{e}} {{f}}", json!({ "a": 1, "b": 2.5, "c": 1e21, "d": 1e-7, "e": 0.1, "f": [1, null, [2, "x"]] })),
+            ("{{obj}} {{missing}} {{n}} {{t}} {{-\"3\"}} {{5n}} {{-5n}} {{1 == 1n}}", json!({ "obj": {}, "n": null, "t": true })),
+            ("{{#each items i}}<{{i.x}}:{{i.x > 1}}>{{/}}", json!({ "items": [{ "x": 1 }, { "x": 2 }] })),
+            ("{{#if a == 1}}one{{/#if a == 2}}two{{/#else}}else{{/}}|{{a || \"z\"}}|{{a && 0}}|{{!a}}", json!({ "a": 2 })),
+            ("{{null == undefined}} {{1 == \"1\"}} {{1 === \"1\"}} {{\" 2 \" == 2}} {{2n === 2}} {{\"apple\" < \"banana\"}}", json!({})),
+            ("{{#let a = 1, b = a == 1, c = u.name}}{{a}} {{b}} {{c}}{{/}}|{{a}}", json!({ "a": "outer", "u": { "name": "N" } })),
+            ("{{#with u as x}}{{x.name}}{{/#else}}none{{/}}|{{#with v x}}{{x}}{{/#else}}none{{/}}", json!({ "u": { "name": "N" }, "v": 0 })),
+            ("{{#unless a}}no{{/#else}}yes{{/}}|{{#switch k}}{{/#case \"a\"}}A{{/#case \"b\" \"c\"}}BC{{/#default}}D{{/}}", json!({ "a": 1, "k": "c" })),
+            ("{{#range 1 5 as i}}{{i}},{{/}}|{{#range 10 1 step=3 i}}{{i}},{{/}}|{{#times 3 as i}}[{{i}}]{{/}}", json!({})),
+            ("{{#range 1 2 step=s i}}{{i}}{{/}}", json!({ "s": "x" })),
+            ("{{#each xs x where=(x.on) sort=\"n\"}}{{x.n}},{{/}}|{{#each xs x sort=\"n\" reverse=true offset=1 limit=2}}{{x.n}},{{/}}", json!({ "xs": [{ "n": 3, "on": true }, { "n": "b", "on": false }, { "n": 1, "on": true }, { "n": null }] })),
+            ("{{#groupBy xs \"t.k\" as g}}{{g.key}}:{{#each g.items i}}{{i.v}}{{/}};{{/}}", json!({ "xs": [{ "t": { "k": "a" }, "v": 1 }, { "t": { "k": "b" }, "v": 2 }, { "t": { "k": "a" }, "v": 3 }] })),
+            ("{{#deepEqual a b}}same{{/#else}}diff{{/}}|{{#deepEqual a c}}same{{/#else}}diff{{/}}", json!({ "a": { "k": [1, { "z": "q" }] }, "b": { "k": [1, { "z": "q" }] }, "c": { "k": [1] } })),
+            ("{{#each a x}}{{x.n}}[{{#each x.kids x}}{{x}},{{/}}]{{x.n}};{{/}}{{x}}", json!({ "a": [{ "n": 1, "kids": [5, 6] }, { "n": 2, "kids": [] }], "x": "data" })),
+            ("{{a.b.c}}", json!({ "a": 1 })),
+            ("{{a + b * 2}} {{(a + b) % 4}} {{a - b - 1}} {{a / 0}} {{-a % 2}} {{s + a}} {{a + s}} {{xs + 1}} {{xs * 2}} {{o + 1}} {{n + u}} {{n + t}} {{s - 1}} {{\" 2 \" * \"3\"}}", json!({ "a": 7, "b": 2, "s": "x", "xs": [5], "o": {}, "n": null, "t": true })),
+            ("{{#let total = price * qty}}{{total}}{{/}} {{#range 1 -n i}}{{i}}{{/}} {{#range 0 n - 1 i}}{{i}}{{/}} {{#times n / 2 i}}{{i}}{{/}}", json!({ "price": 4, "qty": 3, "n": 4 })),
+            ("{{a + 1n}}", json!({ "a": 1 })),
+            ("{{b / 0n}}", json!({})),
+            ("{{170141183460469231731687303715884105727n + 1n}}", json!({})),
+            ("{{#switch k}}{{/#case 1}}one{{/#case \"2\"}}two{{/#default}}other{{/}}|{{#switch k}}{{/#default}}a{{/#default}}b{{/}}", json!({ "k": "1" })),
+            ("{{#and a b}}both{{/#else}}not both{{/}} {{#or a b}}one{{/#else}}none{{/}} {{#and a x.y.z}}x{{/#else}}y{{/}}{{#or b x.y.z}}x{{/}}", json!({ "a": 0, "b": "x" })),
+            ("{{7n / 2n}} {{-7n % 2n}} {{2n * 3n - 1n}} {{5n + \"\"}}", json!({})),
+            ("{{#range 1152921504606846976n 1152921504606846978n i}}{{i}},{{/}}|{{#range 5n 0.5 step=2n i}}{{i}},{{/}}|{{#range 3n 1n step=1 i}}{{i}},{{/}}", json!({})),
+            ("{{#range 1n 2 step=0.5 i}}{{i}}{{/}}", json!({})),
+            ("{{#times n i}}{{/}}", json!({ "n": 1e12 })),
+            ("{{#range 0 n i}}{{/}}", json!({ "n": 1e12 })),
+            ("{{#range -170141183460469231731687303715884105727n 170141183460469231731687303715884105727n i}}{{/}}", json!({})),
+            ("{{#each xs x reverse}}{{x}}{{/}}|{{#each ys y reverse sort=\"n\" limit=2}}{{y.n}}{{/}}", json!({ "xs": [1, 2, 3], "ys": [{ "n": 3 }, { "n": 1 }, { "n": 2 }] })),
+            ("{{#each xs x backwards}}{{/}}", json!({ "xs": [1] })),
+            ("{{#each xs x reverse 1}}{{/}}", json!({ "xs": [1] })),
+            ("{{#each xs x}}{{x}}{{/#else}}none{{/}}", json!({ "xs": [] })),
+            ("{{#each xs x}}{{x * 1}},{{x == 16n}},{{x == 16}};{{/}}", json!({ "xs": ["", " 16 ", "0x10", "0X10", "0o20", "0b10000", "-0x10", "Infinity", "-Infinity", "inf", "NaN", "1_6", "16px", "1.6e1", ".5", "5.", "\u{a0}16\u{feff}", "\u{85}16", "0x", "1e"] }))
+        ]
+    }
+
+    #[test]
+    fn compiled_templates_render_like_rust() {
+        let dir = std::env::temp_dir().join(format!("finch-transpiler-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut compiler = Compiler::new();
+        let mut expected = vec![];
+        let mut script = String::from("const out = [];\n");
+        for (ind, (template, data)) in fixtures().iter().enumerate() {
+            let module = transpile(template, TranspileOptions { parser: ParserOptions::default(), commonjs: true }).unwrap();
+            let path = dir.join(format!("t{}.js", ind));
+            std::fs::write(&path, module).unwrap();
+            script.push_str(&format!("try {{ out.push(require({})({})); }} catch (err) {{ out.push(\"Error: \" + err.message); }}\n", string_literal(&path.to_string_lossy()), data));
+            compiler.add_template(&ind.to_string(), template, ParserOptions::default()).unwrap();
+            expected.push(match compiler.render_json(&ind.to_string(), data) {
+                Ok(res) => res,
+                Err(err) => format!("Error: {}", err)
+            });
+        }
+        script.push_str("console.log(JSON.stringify(out));\n");
+        let output = std::process::Command::new("node").arg("-e").arg(&script).output();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let output = match output {
+            Ok(output) => output,
+            // The rust renderer doesn't need node, only this comparison does
+            Err(_) => {
+                eprintln!("node isn't installed, skipping the comparison with the compiled templates");
+                return;
+            }
+        };
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let rendered: Vec<String> = serde_json::from_slice(&output.stdout).unwrap();
+        for ((template, _), (js, rust)) in fixtures().iter().zip(rendered.iter().zip(expected.iter())) {
+            assert_eq!(js, rust, "{}", template);
+        }
+    }
+
+}
@@ -0,0 +1,664 @@
+use std::sync::Arc;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use serde::{Serialize, Serializer, ser::{Error, SerializeMap, SerializeSeq}};
+use serde_json::Value;
+
+use crate::error::{FinchError, FinchResult};
+
+/// A reference to an object or function owned by a [DataSource]. What the
+/// number means is up to the data source, it's usually an index into a table of handles.
+#[derive(Debug, Clone, Copy)]
+pub struct HostRef(pub usize);
+
+/// A value the renderer works with. Everything which isn't an object, array or
+/// function owned by the host is copied into rust.
+#[derive(Debug, Clone)]
+pub enum RawValue {
+    String(String),
+    Number(f64),
+    BigInt(i128),
+    Boolean(bool),
+    Vec(Arc<Vec<RawValue>>),
+    /// An object created in rust
+    Object(Arc<HashMap<String, RawValue>>),
+    /// An object owned by the data source, its properties are read with [DataSource::prop]
+    HostObject(HostRef),
+    /// An array owned by the data source, its items are only read with [DataSource::item] when they're needed
+    HostArray(HostRef),
+    /// A function owned by the data source, called with [DataSource::call]
+    Function(HostRef),
+    Undefined,
+    Null
+}
+
+/// Where the renderer gets the data it can't read by itself from, and what it
+/// uses to call functions. The node.js bindings implement it over JS handles,
+/// data which lives completely in rust can use [OwnedSource].
+pub trait DataSource {
+
+    /// Reads a property of an object (or function) owned by the data source.
+    /// Properties which don't exist are [RawValue::Undefined].
+    fn prop(&mut self, obj: HostRef, key: &str) -> FinchResult<RawValue>;
+
+    /// The length of an array owned by the data source.
+    fn len(&mut self, arr: HostRef) -> FinchResult<usize> {
+        Ok(self.prop(arr, "length")?.to_number() as usize)
+    }
+
+    /// Reads an item of an array owned by the data source.
+    /// Items past the end are [RawValue::Undefined].
+    fn item(&mut self, arr: HostRef, ind: usize) -> FinchResult<RawValue> {
+        self.prop(arr, &ind.to_string())
+    }
+
+    /// Converts an object or function owned by the data source to text. `None` leaves it to
+    /// the renderer, which writes objects as `[object Object]`. Arrays are converted item by item.
+    fn to_string(&mut self, _obj: HostRef) -> FinchResult<Option<String>> {
+        Ok(None)
+    }
+
+    /// Whether two references are to the same object, array or function.
+    fn same(&mut self, left: HostRef, right: HostRef) -> FinchResult<bool> {
+        Ok(left.0 == right.0)
+    }
+
+    /// The own enumerable keys of an object owned by the data source, used by the `deepEqual` helper.
+    fn keys(&mut self, _obj: HostRef) -> FinchResult<Vec<String>> {
+        Err(FinchError::Custom(String::from("This data source cannot list the keys of objects")))
+    }
+
+    /// Calls a function owned by the data source.
+    fn call(&mut self, func: HostRef, args: Vec<RawValue>) -> FinchResult<RawValue>;
+
+    /// Calls a helper which was registered by the host with [HostHelper](crate::helper::HostHelper).
+    /// `body` is the rendered body of the block, if it has one.
+    fn call_helper(&mut self, name: &str, _params: Vec<RawValue>, _hash: HashMap<String, RawValue>, _body: Option<String>) -> FinchResult<String> {
+        Err(FinchError::HelperNotFound(name.to_string()))
+    }
+
+    /// Evaluates code written in the host language, used by the `js` helper.
+    /// `data` is the data of the template which contains the code.
+    fn eval(&mut self, _code: &str, _data: &RawValue) -> FinchResult<RawValue> {
+        Err(FinchError::Custom(String::from("This data source cannot run code")))
+    }
+
+    /// Whether values the template needs are still on their way, like promises which aren't resolved
+    /// yet. The renderer doesn't stop at errors then, it renders the rest of the template without the
+    /// instructions which failed, and its result is expected to be thrown away.
+    fn pending(&self) -> bool {
+        false
+    }
+
+    /// Receives the output of [Compiler::stream](crate::Compiler::stream), one chunk at a time.
+    fn write(&mut self, _chunk: &str) -> FinchResult<()> {
+        Err(FinchError::Custom(String::from("This data source cannot stream output")))
+    }
+
+}
+
+/// A data source for data which lives in rust. It doesn't own any objects, so
+/// it's only there for templates which don't use host specific helpers.
+pub struct OwnedSource;
+
+impl DataSource for OwnedSource {
+
+    fn prop(&mut self, _obj: HostRef, _key: &str) -> FinchResult<RawValue> {
+        Err(FinchError::ExpectedObject)
+    }
+
+    fn call(&mut self, _func: HostRef, _args: Vec<RawValue>) -> FinchResult<RawValue> {
+        Err(FinchError::NotCallable)
+    }
+
+}
+
+// How deeply nested values can be for `deep_equals` and `stringify`, deeper values are probably circular
+const MAX_DEPTH: usize = 64;
+
+/// Structural equality of values which live in rust, host values are never equal. Templates
+/// use [strict_equals](RawValue::strict_equals) and [loose_equals](RawValue::loose_equals).
+impl PartialEq for RawValue {
+
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::String(left), Self::String(right)) => left == right,
+            (Self::Number(left), Self::Number(right)) => left == right,
+            (Self::BigInt(left), Self::BigInt(right)) => left == right,
+            (Self::Boolean(left), Self::Boolean(right)) => left == right,
+            (Self::Vec(left), Self::Vec(right)) => left == right,
+            (Self::Object(left), Self::Object(right)) => Arc::ptr_eq(left, right),
+            (Self::Undefined, Self::Undefined) | (Self::Null, Self::Null) => true,
+            // Host values can't be compared without the data source
+            _ => false
+        }
+    }
+}
+
+impl RawValue {
+
+    pub fn is_falsey(&self) -> bool {
+        match self {
+            Self::String(str) => str.is_empty(),
+            Self::Number(num) => *num == 0.0 || num.is_nan(),
+            Self::BigInt(num) => *num == 0,
+            Self::Boolean(bol) => !(*bol),
+            Self::Null | Self::Undefined => true,
+            Self::Vec(_) | Self::Object(_) | Self::HostObject(_) | Self::HostArray(_) | Self::Function(_) => false
+        }
+    }
+
+    pub fn to_number(&self) -> f64 {
+        match self {
+            Self::Number(num) => *num,
+            Self::BigInt(num) => *num as f64,
+            Self::Boolean(bol) => if *bol { 1.0 } else { 0.0 },
+            Self::Null => 0.0,
+            Self::String(st) => string_to_number(st),
+            Self::Undefined | Self::Vec(_) | Self::Object(_) | Self::HostObject(_) | Self::HostArray(_) | Self::Function(_) => f64::NAN
+        }
+    }
+
+    pub fn into_string(self) -> String {
+        match self {
+            Self::String(st) => st,
+            other => other.to_string()
+        }
+    }
+
+    /// Reads a property of the value, the same way `value.key` works in a template.
+    pub fn get(&self, key: &str, source: &mut dyn DataSource) -> FinchResult<RawValue> {
+        match self {
+            Self::Object(obj) => Ok(obj.get(key).cloned().unwrap_or(RawValue::Undefined)),
+            Self::HostObject(obj) | Self::Function(obj) => source.prop(*obj, key),
+            Self::Vec(vec) => {
+                if key == "length" {
+                    Ok(RawValue::Number(vec.len() as f64))
+                } else {
+                    Ok(key.parse::<usize>().ok().and_then(|ind| vec.get(ind)).cloned().unwrap_or(RawValue::Undefined))
+                }
+            },
+            Self::HostArray(arr) => {
+                if key == "length" {
+                    Ok(RawValue::Number(source.len(*arr)? as f64))
+                } else if let Ok(ind) = key.parse::<usize>() {
+                    source.item(*arr, ind)
+                } else {
+                    Ok(RawValue::Undefined)
+                }
+            },
+            _ => Err(FinchError::ExpectedObject)
+        }
+    }
+
+    /// The number of items of an array, `None` if the value isn't one.
+    pub fn array_len(&self, source: &mut dyn DataSource) -> FinchResult<Option<usize>> {
+        match self {
+            Self::Vec(vec) => Ok(Some(vec.len())),
+            Self::HostArray(arr) => source.len(*arr).map(Some),
+            _ => Ok(None)
+        }
+    }
+
+    /// Reads an item of an array, without copying the other items of arrays owned by the data source.
+    pub fn item(&self, ind: usize, source: &mut dyn DataSource) -> FinchResult<RawValue> {
+        match self {
+            Self::Vec(vec) => Ok(vec.get(ind).cloned().unwrap_or(RawValue::Undefined)),
+            Self::HostArray(arr) => source.item(*arr, ind),
+            _ => Err(FinchError::ExpectedObject)
+        }
+    }
+
+    /// Converts the value to the text a template renders for it, like `String(value)` in javascript.
+    /// Unlike `to_string`, this can read the items of arrays owned by the data source, and lets it
+    /// convert its objects and functions with [DataSource::to_string].
+    pub fn stringify(&self, source: &mut dyn DataSource) -> FinchResult<String> {
+        self.stringify_at(source, 0)
+    }
+
+    fn stringify_at(&self, source: &mut dyn DataSource, depth: usize) -> FinchResult<String> {
+        if let Self::HostObject(obj) | Self::Function(obj) = self {
+            return Ok(source.to_string(*obj)?.unwrap_or_else(|| self.to_string()));
+        }
+        match self.array_len(source)? {
+            Some(len) => {
+                if depth > MAX_DEPTH {
+                    return Err(FinchError::Custom(String::from("The array is nested too deeply to print, it might be circular")));
+                }
+                let mut items = Vec::with_capacity(len);
+                for ind in 0..len {
+                    items.push(match self.item(ind, source)? {
+                        Self::Null | Self::Undefined => String::new(),
+                        item => item.stringify_at(source, depth + 1)?
+                    });
+                }
+                Ok(items.join(","))
+            },
+            None => Ok(self.to_string())
+        }
+    }
+
+    /// Compares two values with `===`. Objects, arrays and functions are only equal to themselves.
+    pub fn strict_equals(&self, other: &RawValue, source: &mut dyn DataSource) -> FinchResult<bool> {
+        Ok(match (self, other) {
+            (Self::Vec(left), Self::Vec(right)) => Arc::ptr_eq(left, right),
+            (Self::HostObject(left) | Self::HostArray(left) | Self::Function(left), Self::HostObject(right) | Self::HostArray(right) | Self::Function(right)) => source.same(*left, *right)?,
+            _ => self == other
+        })
+    }
+
+    /// Compares two values with `==`, like javascript does. `null` and `undefined` are equal, and
+    /// numbers, strings, BigInts and booleans are converted to numbers when their types differ.
+    /// Unlike in javascript, objects are never equal to values which aren't objects.
+    pub fn loose_equals(&self, other: &RawValue, source: &mut dyn DataSource) -> FinchResult<bool> {
+        Ok(match (self, other) {
+            (Self::Null | Self::Undefined, Self::Null | Self::Undefined) => true,
+            (Self::Null | Self::Undefined, _) | (_, Self::Null | Self::Undefined) => false,
+            (Self::Boolean(left), Self::Boolean(right)) => left == right,
+            (Self::Boolean(_), _) => RawValue::Number(self.to_number()).loose_equals(other, source)?,
+            (_, Self::Boolean(_)) => self.loose_equals(&RawValue::Number(other.to_number()), source)?,
+            (Self::Number(num), Self::String(_)) => *num == other.to_number(),
+            (Self::String(_), Self::Number(num)) => self.to_number() == *num,
+            (Self::BigInt(big), Self::String(st)) | (Self::String(st), Self::BigInt(big)) => {
+                let trimmed = js_trim(st);
+                if trimmed.is_empty() {
+                    *big == 0
+                } else if let Some((radix, digits)) = radix_prefix(trimmed) {
+                    !digits.starts_with(['+', '-']) && i128::from_str_radix(digits, radix).is_ok_and(|num| num == *big)
+                } else {
+                    trimmed.parse::<i128>().is_ok_and(|num| num == *big)
+                }
+            },
+            (Self::BigInt(big), Self::Number(num)) | (Self::Number(num), Self::BigInt(big)) => {
+                num.fract() == 0.0 && num.abs() < 2f64.powi(127) && *num as i128 == *big
+            },
+            _ => self.strict_equals(other, source)?
+        })
+    }
+
+    /// Compares the contents of two values, used by the `deepEqual` helper. Arrays are equal if their
+    /// items are, objects if they have the same keys with equal values. Everything else is compared with `===`.
+    pub fn deep_equals(&self, other: &RawValue, source: &mut dyn DataSource) -> FinchResult<bool> {
+        self.deep_equals_at(other, source, 0)
+    }
+
+    fn deep_equals_at(&self, other: &RawValue, source: &mut dyn DataSource, depth: usize) -> FinchResult<bool> {
+        if depth > MAX_DEPTH {
+            return Err(FinchError::Custom(String::from("The values are nested too deeply to compare, they might be circular")));
+        }
+        if self.strict_equals(other, source)? {
+            return Ok(true);
+        }
+        if let (Some(len), Some(other_len)) = (self.array_len(source)?, other.array_len(source)?) {
+            if len != other_len {
+                return Ok(false);
+            }
+            for ind in 0..len {
+                if !self.item(ind, source)?.deep_equals_at(&other.item(ind, source)?, source, depth + 1)? {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
+        let (mut keys, mut other_keys) = match (self.keys(source)?, other.keys(source)?) {
+            (Some(keys), Some(other_keys)) => (keys, other_keys),
+            _ => return Ok(false)
+        };
+        keys.sort();
+        other_keys.sort();
+        if keys != other_keys {
+            return Ok(false);
+        }
+        for key in &keys {
+            if !self.get(key, source)?.deep_equals_at(&other.get(key, source)?, source, depth + 1)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // The keys of an object, `None` if the value isn't a plain object
+    fn keys(&self, source: &mut dyn DataSource) -> FinchResult<Option<Vec<String>>> {
+        match self {
+            Self::Object(obj) => Ok(Some(obj.keys().cloned().collect())),
+            Self::HostObject(obj) => source.keys(*obj).map(Some),
+            _ => Ok(None)
+        }
+    }
+
+    /// Copies arrays owned by the data source into rust, including the ones nested in arrays.
+    pub fn copy(&self, source: &mut dyn DataSource) -> FinchResult<RawValue> {
+        match self.array_len(source)? {
+            Some(len) => {
+                let mut items = Vec::with_capacity(len);
+                for ind in 0..len {
+                    items.push(self.item(ind, source)?.copy(source)?);
+                }
+                Ok(RawValue::Vec(Arc::new(items)))
+            },
+            None => Ok(self.clone())
+        }
+    }
+
+}
+
+impl std::fmt::Display for RawValue {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String(st) => write!(f, "{}", st),
+            Self::Number(num) => write!(f, "{}", format_number(*num)),
+            Self::BigInt(num) => write!(f, "{}", num),
+            Self::Boolean(bol) => write!(f, "{}", bol),
+            Self::Undefined => write!(f, "undefined"),
+            Self::Null => write!(f, "null"),
+            Self::Vec(v) => write!(f, "{}", v.iter().map(|val| match val {
+                Self::Null | Self::Undefined => String::new(),
+                val => val.to_string()
+            }).collect::<Vec<String>>().join(",")),
+            Self::Object(_) | Self::HostObject(_) => write!(f, "[object Object]"),
+            // The items can only be read with the data source, see `stringify`
+            Self::HostArray(_) => write!(f, "[object Array]"),
+            // The source of the function can only be read with the data source
+            Self::Function(_) => write!(f, "function () {{ [native code] }}")
+        }
+    }
+}
+
+impl From<&str> for RawValue {
+    fn from(val: &str) -> Self {
+        Self::String(val.to_string())
+    }
+}
+
+impl From<String> for RawValue {
+    fn from(val: String) -> Self {
+        Self::String(val)
+    }
+}
+
+impl From<f64> for RawValue {
+    fn from(val: f64) -> Self {
+        Self::Number(val)
+    }
+}
+
+impl From<bool> for RawValue {
+    fn from(val: bool) -> Self {
+        Self::Boolean(val)
+    }
+}
+
+impl From<Vec<RawValue>> for RawValue {
+    fn from(val: Vec<RawValue>) -> Self {
+        Self::Vec(Arc::new(val))
+    }
+}
+
+impl From<HashMap<String, RawValue>> for RawValue {
+    fn from(val: HashMap<String, RawValue>) -> Self {
+        Self::Object(Arc::new(val))
+    }
+}
+
+impl From<&Value> for RawValue {
+    fn from(val: &Value) -> Self {
+        match val {
+            Value::Null => Self::Null,
+            Value::Bool(bol) => Self::Boolean(*bol),
+            Value::Number(num) => Self::Number(num.as_f64().unwrap_or(f64::NAN)),
+            Value::String(st) => Self::String(st.clone()),
+            Value::Array(arr) => Self::Vec(Arc::new(arr.iter().map(RawValue::from).collect())),
+            Value::Object(obj) => Self::Object(Arc::new(obj.iter().map(|(key, val)| (key.clone(), RawValue::from(val))).collect()))
+        }
+    }
+}
+
+impl RawValue {
+
+    /// Converts any serializable value, the same way `serde_json` would.
+    pub fn from_serialize<T: Serialize + ?Sized>(val: &T) -> FinchResult<Self> {
+        serde_json::to_value(val).map(|val| Self::from(&val)).map_err(|err| FinchError::External(err.to_string()))
+    }
+
+    /// Converts the value to JSON. Fails for values owned by the data source, arrays
+    /// can be [copied](Self::copy) first.
+    pub fn to_json(&self) -> FinchResult<Value> {
+        serde_json::to_value(self).map_err(|err| FinchError::External(err.to_string()))
+    }
+
+}
+
+/// BigInts are written as strings, and numbers which aren't finite as `null`, just like `JSON.stringify`.
+impl Serialize for RawValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::String(st) => serializer.serialize_str(st),
+            Self::Number(num) => if num.is_finite() { serializer.serialize_f64(*num) } else { serializer.serialize_unit() },
+            Self::BigInt(num) => serializer.serialize_str(&num.to_string()),
+            Self::Boolean(bol) => serializer.serialize_bool(*bol),
+            Self::Vec(vec) => {
+                let mut seq = serializer.serialize_seq(Some(vec.len()))?;
+                for item in vec.iter() {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            },
+            Self::Object(obj) => {
+                let mut map = serializer.serialize_map(Some(obj.len()))?;
+                for (key, val) in obj.iter() {
+                    map.serialize_entry(key, val)?;
+                }
+                map.end()
+            },
+            Self::HostObject(_) | Self::HostArray(_) | Self::Function(_) => Err(S::Error::custom("Values owned by the data source can't be serialized")),
+            Self::Undefined | Self::Null => serializer.serialize_unit()
+        }
+    }
+}
+
+/// The arithmetic operators, `+`, `-`, `*`, `/` and `%`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arithmetic {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem
+}
+
+/// Applies an arithmetic operator like javascript does. `+` joins the values as text when one of
+/// them is a string, an array or an object, the other operators convert them to numbers. BigInts
+/// can only be used with other BigInts, and fail with [FinchError::BigIntOverflow] when the result
+/// doesn't fit in an i128.
+pub fn arithmetic(op: Arithmetic, left: &RawValue, right: &RawValue, source: &mut dyn DataSource) -> FinchResult<RawValue> {
+    let is_object = |val: &RawValue| matches!(val, RawValue::Vec(_) | RawValue::Object(_) | RawValue::HostObject(_) | RawValue::HostArray(_) | RawValue::Function(_));
+    if op == Arithmetic::Add && (matches!(left, RawValue::String(_)) || matches!(right, RawValue::String(_)) || is_object(left) || is_object(right)) {
+        return Ok(RawValue::String(left.stringify(source)? + &right.stringify(source)?));
+    }
+    // Objects become numbers through their text, so `[5] * 2` is 10
+    let mut numeric = |val: &RawValue| -> FinchResult<RawValue> {
+        if is_object(val) { Ok(RawValue::Number(RawValue::String(val.stringify(source)?).to_number())) } else { Ok(val.clone()) }
+    };
+    match (numeric(left)?, numeric(right)?) {
+        (RawValue::BigInt(left), RawValue::BigInt(right)) => {
+            if matches!(op, Arithmetic::Div | Arithmetic::Rem) && right == 0 {
+                return Err(FinchError::DivisionByZero);
+            }
+            let res = match op {
+                Arithmetic::Add => left.checked_add(right),
+                Arithmetic::Sub => left.checked_sub(right),
+                Arithmetic::Mul => left.checked_mul(right),
+                Arithmetic::Div => left.checked_div(right),
+                Arithmetic::Rem => left.checked_rem(right)
+            };
+            res.map(RawValue::BigInt).ok_or(FinchError::BigIntOverflow)
+        },
+        (RawValue::BigInt(_), _) | (_, RawValue::BigInt(_)) => Err(FinchError::MixedBigInt),
+        (left, right) => {
+            let (left, right) = (left.to_number(), right.to_number());
+            Ok(RawValue::Number(match op {
+                Arithmetic::Add => left + right,
+                Arithmetic::Sub => left - right,
+                Arithmetic::Mul => left * right,
+                Arithmetic::Div => left / right,
+                Arithmetic::Rem => left % right
+            }))
+        }
+    }
+}
+
+/// Compares two values with `>`, `<`, `>=` and `<=`. Strings are ordered by their characters, and
+/// fail with [FinchError::NotNumbers] if the values aren't both numbers or both strings.
+pub fn compare_values(left: &RawValue, right: &RawValue) -> FinchResult<Ordering> {
+    match (left, right) {
+        (RawValue::String(left), RawValue::String(right)) => Ok(left.cmp(right)),
+        _ => compare_numbers(left, right)
+    }
+}
+
+/// Compares two values with `>`, `<`, `>=` and `<=`, fails with [FinchError::NotNumbers] if one isn't a number.
+pub fn compare_numbers(left: &RawValue, right: &RawValue) -> FinchResult<Ordering> {
+    if !matches!(left, RawValue::Number(_) | RawValue::BigInt(_)) || !matches!(right, RawValue::Number(_) | RawValue::BigInt(_)) {
+        return Err(FinchError::NotNumbers);
+    }
+    if let (RawValue::BigInt(num_left), RawValue::BigInt(num_right)) = (left, right) {
+        return Ok(num_left.cmp(num_right));
+    }
+    let (num_left, num_right) = (left.to_number(), right.to_number());
+    Ok(if num_left > num_right { Ordering::Greater }
+    else if num_left == num_right { Ordering::Equal }
+    else { Ordering::Less })
+}
+
+/// Writes a number like javascript's `String(number)`: with the shortest digits which read
+/// back as the same number, and with an exponent if it's at least 1e21 or smaller than 1e-6.
+pub fn format_number(num: f64) -> String {
+    if num.is_nan() {
+        return String::from("NaN");
+    }
+    if num.is_infinite() {
+        return String::from(if num > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    // -0 too
+    if num == 0.0 {
+        return String::from("0");
+    }
+    let sign = if num < 0.0 { "-" } else { "" };
+    // The shortest form can round a tie up, javascript picks the closest digits of the same length, or the even one
+    let shortest = format!("{:e}", num.abs());
+    let len = shortest.split_once('e').unwrap().0.replace('.', "").len();
+    let exp_form = format!("{:.*e}", len - 1, num.abs());
+    let (mantissa, exp) = exp_form.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let len = len as i32;
+    // How many digits are before the decimal point
+    let point = exp.parse::<i32>().unwrap() + 1;
+    if len <= point && point <= 21 {
+        format!("{}{}{}", sign, digits, "0".repeat((point - len) as usize))
+    } else if 0 < point && point <= 21 {
+        format!("{}{}.{}", sign, &digits[..point as usize], &digits[point as usize..])
+    } else if -6 < point && point <= 0 {
+        format!("{}0.{}{}", sign, "0".repeat(-point as usize), digits)
+    } else {
+        let mantissa = if len == 1 { digits } else { format!("{}.{}", &digits[..1], &digits[1..]) };
+        format!("{}{}e{}{}", sign, mantissa, if point > 0 { "+" } else { "-" }, (point - 1).abs())
+    }
+}
+
+/// Reads a string like javascript's `Number(string)`. Surrounding whitespace is ignored and an empty
+/// string is `0`. It takes decimal numbers with an optional sign and exponent, the exact words
+/// `Infinity`, `+Infinity` and `-Infinity`, and unsigned `0x`, `0o` and `0b` integers. Anything else is NaN.
+pub fn string_to_number(st: &str) -> f64 {
+    let trimmed = js_trim(st);
+    match trimmed {
+        "" => return 0.0,
+        "Infinity" | "+Infinity" => return f64::INFINITY,
+        "-Infinity" => return f64::NEG_INFINITY,
+        _ => {}
+    }
+    if let Some((radix, digits)) = radix_prefix(trimmed) {
+        return parse_radix(digits, radix).unwrap_or(f64::NAN);
+    }
+    // Rust also reads inf and nan, which are the only letters it takes besides the exponent
+    if trimmed.bytes().all(|ch| ch.is_ascii_digit() || matches!(ch, b'.' | b'e' | b'E' | b'+' | b'-')) {
+        trimmed.parse().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+// Javascript's whitespace: unicode spaces and line breaks, and the byte order mark, but not U+0085
+fn js_trim(st: &str) -> &str {
+    st.trim_matches(|ch: char| (ch.is_whitespace() && ch != '\u{85}') || ch == '\u{feff}')
+}
+
+// The radix and digits of an integer written with a 0x, 0o or 0b prefix
+fn radix_prefix(st: &str) -> Option<(u32, &str)> {
+    let radix = match st.get(..2)? {
+        "0x" | "0X" => 16,
+        "0o" | "0O" => 8,
+        "0b" | "0B" => 2,
+        _ => return None
+    };
+    Some((radix, &st[2..]))
+}
+
+// The radix is a power of two, so keeping the first 124 or more bits, and whether any bit after them
+// is set, is enough to round to the closest number
+fn parse_radix(digits: &str, radix: u32) -> Option<f64> {
+    if digits.is_empty() {
+        return None;
+    }
+    let bits = radix.trailing_zeros();
+    let (mut mantissa, mut exp, mut rest) = (0u128, 0, false);
+    for ch in digits.chars() {
+        let digit = ch.to_digit(radix)? as u128;
+        if mantissa >> (127 - bits) == 0 {
+            mantissa = mantissa << bits | digit;
+        } else {
+            exp += bits as i32;
+            rest |= digit != 0;
+        }
+    }
+    Some((mantissa | rest as u128) as f64 * 2f64.powi(exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_numbers_like_javascript() {
+        let cases = [
+            (-0.0, "0"), (1e21, "1e+21"), (1e-7, "1e-7"), (-1e-7, "-1e-7"), (1.5e-7, "1.5e-7"), (0.000001, "0.000001"), (5e-7, "5e-7"),
+            (1e20, "100000000000000000000"), (123456789012345680000.0, "123456789012345680000"), (999999999999999900000.0, "999999999999999900000"),
+            (1.0 / 3.0, "0.3333333333333333"), (5e-324, "5e-324"), (f64::MAX, "1.7976931348623157e+308"), (0.1 + 0.2, "0.30000000000000004"),
+            (100.0, "100"), (-123.456, "-123.456"), (9007199254740992.0, "9007199254740992"), (1.23e-18, "1.23e-18"), (4.35, "4.35"),
+            (f64::NAN, "NaN"), (f64::INFINITY, "Infinity"), (f64::NEG_INFINITY, "-Infinity")
+        ];
+        for (num, text) in cases {
+            assert_eq!(format_number(num), text, "{:?}", num);
+        }
+        assert_eq!(RawValue::Number(-0.0).to_string(), "0");
+        assert_eq!(RawValue::Vec(Arc::new(vec![RawValue::Number(1e21), RawValue::Null, RawValue::Number(-0.0)])).stringify(&mut OwnedSource).unwrap(), "1e+21,,0");
+    }
+
+    #[test]
+    fn strings_to_numbers_like_javascript() {
+        let cases = [
+            ("", 0.0), (" \n\t ", 0.0), (" 12 ", 12.0), ("\u{a0}1.5\u{feff}", 1.5), ("-.5e1", -5.0), ("5.", 5.0), ("1.e2", 100.0), ("+3", 3.0),
+            ("0x1F", 31.0), ("0XfF", 255.0), ("0o17", 15.0), ("0b101", 5.0), ("0x20000000000001", 9007199254740992.0),
+            ("Infinity", f64::INFINITY), ("+Infinity", f64::INFINITY), ("-Infinity", f64::NEG_INFINITY)
+        ];
+        for (st, num) in cases {
+            assert_eq!(string_to_number(st), num, "{:?}", st);
+        }
+        assert_eq!(string_to_number(&format!("0x1{}", "0".repeat(256))), f64::INFINITY);
+        assert_eq!(string_to_number(&format!("0x{}", "f".repeat(40))), 2f64.powi(160));
+        for st in ["inf", "-inf", "infinity", "NaN", "nan", "-0x10", "+0x10", "0x", "0x1g", "0b2", "1_000", "1e", "e5", ".", "--1", "1 2", "\u{85}1", "12px"] {
+            assert!(string_to_number(st).is_nan(), "{:?}", st);
+        }
+    }
+
+}
@@ -0,0 +1,169 @@
+//! Traversal of the syntax tree.
+//!
+//! Every `visit_*` method calls the matching `walk_*` function by default, which visits the
+//! children of the node. Override a method to inspect a node, and call `walk_*` from it to keep
+//! going deeper.
+//!
+//! ```
+//! use finch_core::ast::ExpressionKind;
+//! use finch_core::parser::{Parser, ParserOptions};
+//! use finch_core::visit::{Visitor, walk_expression};
+//!
+//! struct Variables(Vec<String>);
+//!
+//! impl Visitor for Variables {
+//!     fn visit_expression(&mut self, exp: &ExpressionKind) {
+//!         if let ExpressionKind::Var(name) = exp {
+//!             self.0.push(name.clone());
+//!         }
+//!         walk_expression(self, exp);
+//!     }
+//! }
+//!
+//! let ast = Parser::parse("{{a}} {{#if b == c}}{{d}}{{/}}", ParserOptions::default()).unwrap();
+//! let mut vars = Variables(vec![]);
+//! vars.visit_subtext(&ast);
+//! assert_eq!(vars.0, ["a", "b", "c", "d"]);
+//! ```
+
+use std::ops::Range;
+use crate::ast::*;
+
+pub trait Visitor {
+    fn visit_subtext(&mut self, text: &SubText) {
+        walk_subtext(self, text);
+    }
+
+    fn visit_template(&mut self, template: &Template) {
+        walk_template(self, template);
+    }
+
+    /// Static text between tags
+    fn visit_text(&mut self, _text: &str, _pos: &Range<usize>) {}
+
+    /// Called for the block of a template, and for each of its followups
+    fn visit_block(&mut self, block: &FnBlock) {
+        walk_block(self, block);
+    }
+
+    fn visit_expression(&mut self, exp: &ExpressionKind) {
+        walk_expression(self, exp);
+    }
+}
+
+pub fn walk_subtext<V: Visitor + ?Sized>(visitor: &mut V, text: &SubText) {
+    for template in &text.templates {
+        visitor.visit_template(template);
+    }
+}
+
+pub fn walk_template<V: Visitor + ?Sized>(visitor: &mut V, template: &Template) {
+    match &template.kind {
+        TemplateKind::Text(text) => visitor.visit_text(text, &template.pos),
+        TemplateKind::Expression(exp) => visitor.visit_expression(exp),
+        TemplateKind::Block(block) => visitor.visit_block(block),
+        TemplateKind::Error => {}
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &FnBlock) {
+    for param in &block.params {
+        visitor.visit_expression(param);
+    }
+    for (_, value) in &block.hash {
+        visitor.visit_expression(value);
+    }
+    if let Some(body) = &block.block {
+        visitor.visit_subtext(body);
+    }
+    if let Some(chain) = &block.chain {
+        visitor.visit_block(chain);
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, exp: &ExpressionKind) {
+    match exp {
+        ExpressionKind::Binary(op) => {
+            let (left, right) = op.operands();
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        },
+        ExpressionKind::Unary(op) => visitor.visit_expression(op.operand()),
+        ExpressionKind::Call { var, params } => {
+            visitor.visit_expression(var);
+            for param in params {
+                visitor.visit_expression(param);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Like [Visitor], but can modify the tree in place.
+pub trait VisitorMut {
+    fn visit_subtext_mut(&mut self, text: &mut SubText) {
+        walk_subtext_mut(self, text);
+    }
+
+    fn visit_template_mut(&mut self, template: &mut Template) {
+        walk_template_mut(self, template);
+    }
+
+    fn visit_text_mut(&mut self, _text: &mut String, _pos: &mut Range<usize>) {}
+
+    fn visit_block_mut(&mut self, block: &mut FnBlock) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_expression_mut(&mut self, exp: &mut ExpressionKind) {
+        walk_expression_mut(self, exp);
+    }
+}
+
+pub fn walk_subtext_mut<V: VisitorMut + ?Sized>(visitor: &mut V, text: &mut SubText) {
+    for template in &mut text.templates {
+        visitor.visit_template_mut(template);
+    }
+}
+
+pub fn walk_template_mut<V: VisitorMut + ?Sized>(visitor: &mut V, template: &mut Template) {
+    match &mut template.kind {
+        TemplateKind::Text(text) => visitor.visit_text_mut(text, &mut template.pos),
+        TemplateKind::Expression(exp) => visitor.visit_expression_mut(exp),
+        TemplateKind::Block(block) => visitor.visit_block_mut(block),
+        TemplateKind::Error => {}
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut FnBlock) {
+    for param in &mut block.params {
+        visitor.visit_expression_mut(param);
+    }
+    for (_, value) in &mut block.hash {
+        visitor.visit_expression_mut(value);
+    }
+    if let Some(body) = &mut block.block {
+        visitor.visit_subtext_mut(body);
+    }
+    if let Some(chain) = &mut block.chain {
+        visitor.visit_block_mut(chain);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, exp: &mut ExpressionKind) {
+    match exp {
+        ExpressionKind::Binary(op) => {
+            let (left, right) = op.operands_mut();
+            visitor.visit_expression_mut(left);
+            visitor.visit_expression_mut(right);
+        },
+        ExpressionKind::Unary(op) => visitor.visit_expression_mut(op.operand_mut()),
+        ExpressionKind::Call { var, params } => {
+            visitor.visit_expression_mut(var);
+            for param in params {
+                visitor.visit_expression_mut(param);
+            }
+        },
+        _ => {}
+    }
+}
@@ -1,19 +1,32 @@
 use neon::prelude::*;
-mod parser;
-mod error;
-mod convert;
-mod default_helpers;
-mod memory;
-mod compiler;
-mod transpiler;
-use compiler::Compiler;
-use std::{sync::Mutex};
+use neon::result::Throw;
+mod node;
+mod pool;
+use finch_core::{ast, visit, format, transpiler};
+use transpiler::TranspileOptions;
+use finch_core::compiler::Compiler;
+use finch_core::helper::HostHelper;
+use finch_core::optimize::optimize;
+use finch_core::parser::{Parser, ParserOptions};
+use format::FormatOptions;
+use visit::VisitorMut;
+use node::{AsyncCalls, NodeSource, Stringify};
+use pool::Pool;
+use std::ops::Range;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
 
 #[macro_use]
 extern crate lazy_static;
 
+// How often renderAsync renders a template before it gives up
+const MAX_ASYNC_RENDERS: f64 = 100.0;
+
 lazy_static! {
-    static ref COMPILER: Mutex<Compiler> = Mutex::new(Compiler::new());
+    static ref COMPILER: RwLock<Compiler> = RwLock::new(Compiler::new());
+    // Helpers added with addHelper, the compiler calls them through NodeSource
+    static ref JS_HELPERS: Mutex<HashMap<String, Root<JsFunction>>> = Mutex::new(HashMap::new());
+    static ref POOL: Pool = Pool::new(std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(4));
 }
 
 #[neon::main]
@@ -22,31 +35,391 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("addTemplate", |mut cx: FunctionContext| -> JsResult<JsUndefined> {
         let name = cx.argument::<JsString>(0)?.value(&mut cx);
         let value = cx.argument::<JsString>(1)?.value(&mut cx);
-        COMPILER.lock().unwrap().add_template(&name, &value).unwrap();
+        let options = parser_options(&mut cx, 2)?;
+        let res = COMPILER.write().unwrap().add_template(&name, &value, options);
+        match res {
+            Ok(()) => Ok(cx.undefined()),
+            Err(err) => cx.throw_error(err.to_string())
+        }
+    })?;
+
+    cx.export_function("loadBundle", |mut cx: FunctionContext| -> JsResult<JsUndefined> {
+        let buffer = cx.argument::<JsBuffer>(0)?;
+        let bytes = cx.borrow(&buffer, |data| data.as_slice::<u8>().to_vec());
+        if let Err(err) = COMPILER.write().unwrap().load_bundle(&bytes) {
+            return cx.throw_error(err.to_string());
+        }
         Ok(cx.undefined())
     })?;
 
+    cx.export_function("bundle", |mut cx: FunctionContext| -> JsResult<JsBuffer> {
+        let bytes = match COMPILER.read().unwrap().bundle() {
+            Ok(bytes) => bytes,
+            Err(err) => return cx.throw_error(err.to_string())
+        };
+        let mut buffer = JsBuffer::new(&mut cx, bytes.len() as u32)?;
+        cx.borrow_mut(&mut buffer, |data| data.as_mut_slice::<u8>().copy_from_slice(&bytes));
+        Ok(buffer)
+    })?;
+
+    cx.export_function("validateTemplate", |mut cx: FunctionContext| -> JsResult<JsArray> {
+        let source = cx.argument::<JsString>(0)?.value(&mut cx);
+        let options = parser_options(&mut cx, 1)?;
+        let (mut ast, mut diagnostics) = Parser::parse_recovering(&source, options);
+        diagnostics.extend(optimize(&mut ast));
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        let res = JsArray::new(&mut cx, diagnostics.len() as u32);
+        for (ind, diagnostic) in diagnostics.iter().enumerate() {
+            let (line, column) = diagnostic.line_col(&source);
+            let obj = cx.empty_object();
+            let message = cx.string(diagnostic.error.to_string());
+            obj.set(&mut cx, "message", message)?;
+            let severity = cx.string(diagnostic.severity.to_string());
+            obj.set(&mut cx, "severity", severity)?;
+            // JS strings are indexed in UTF-16 code units
+            let start = cx.number(source[..diagnostic.span.start].encode_utf16().count() as f64);
+            obj.set(&mut cx, "start", start)?;
+            let end = cx.number(source[..diagnostic.span.end].encode_utf16().count() as f64);
+            obj.set(&mut cx, "end", end)?;
+            let line = cx.number(line as f64);
+            obj.set(&mut cx, "line", line)?;
+            let column = cx.number(column as f64);
+            obj.set(&mut cx, "column", column)?;
+            res.set(&mut cx, ind as u32, obj)?;
+        }
+        Ok(res)
+    })?;
+
+    cx.export_function("parse", |mut cx: FunctionContext| -> JsResult<JsValue> {
+        let source = cx.argument::<JsString>(0)?.value(&mut cx);
+        let options = parser_options(&mut cx, 1)?;
+        let mut ast = match Parser::parse(&source, options) {
+            Ok(ast) => ast,
+            Err(err) => return cx.throw_error(err.to_string())
+        };
+        Utf16Positions::new(&source).visit_subtext_mut(&mut ast);
+        let json = match serde_json::to_string(&ast) {
+            Ok(json) => cx.string(json),
+            Err(err) => return cx.throw_error(err.to_string())
+        };
+        let json_parse = cx.global().get(&mut cx, "JSON")?.downcast_or_throw::<JsObject, _>(&mut cx)?.get(&mut cx, "parse")?.downcast_or_throw::<JsFunction, _>(&mut cx)?;
+        let undefined = cx.undefined();
+        json_parse.call(&mut cx, undefined, vec![json])
+    })?;
+
+    cx.export_function("format", |mut cx: FunctionContext| -> JsResult<JsString> {
+        let source = cx.argument::<JsString>(0)?.value(&mut cx);
+        let mut options = FormatOptions { parser: parser_options(&mut cx, 1)?, indent: None };
+        if let Some(opts) = cx.argument_opt(1) {
+            if let Ok(opts) = opts.downcast::<JsObject, _>(&mut cx) {
+                let indent = opts.get(&mut cx, "indent")?;
+                options.indent = indent.downcast::<JsNumber, _>(&mut cx).ok().map(|num| num.value(&mut cx) as usize);
+            }
+        }
+        match format::format(&source, options) {
+            Ok(res) => Ok(cx.string(res)),
+            Err(err) => cx.throw_error(err.to_string())
+        }
+    })?;
+
+    cx.export_function("transpile", |mut cx: FunctionContext| -> JsResult<JsString> {
+        let source = cx.argument::<JsString>(0)?.value(&mut cx);
+        let mut options = TranspileOptions { parser: parser_options(&mut cx, 1)?, commonjs: false };
+        if let Some(opts) = cx.argument_opt(1) {
+            if let Ok(opts) = opts.downcast::<JsObject, _>(&mut cx) {
+                let commonjs = opts.get(&mut cx, "commonjs")?;
+                options.commonjs = commonjs.downcast::<JsBoolean, _>(&mut cx).map(|b| b.value(&mut cx)).unwrap_or(false);
+            }
+        }
+        match transpiler::transpile(&source, options) {
+            Ok(res) => Ok(cx.string(res)),
+            Err(err) => cx.throw_error(err.to_string())
+        }
+    })?;
+
     cx.export_function("compile", |mut cx: FunctionContext| -> JsResult<JsString> {
         let name = cx.argument::<JsString>(0)?.value(&mut cx);
-        let data= cx.argument::<JsObject>(1)?;
-        match COMPILER.lock().unwrap().compile(&mut cx, &name, data) {
+        let data = cx.argument::<JsObject>(1)?.upcast::<JsValue>();
+        let opts = cx.argument_opt(2);
+        let stringify = stringify_option(&mut cx, opts)?;
+        let compiler = COMPILER.read().unwrap();
+        let helpers = JS_HELPERS.lock().unwrap();
+        let (res, threw) = {
+            let mut source = NodeSource::new(&mut cx, &helpers);
+            source.stringify = stringify;
+            let data = source.raw(data);
+            (compiler.compile(&name, data, &mut source), source.threw)
+        };
+        match res {
             Ok(res) => Ok(cx.string(res)),
-            Err(err) => panic!("{}", err)
+            // Rethrow the exception of the JS function
+            Err(_) if threw => Err(Throw),
+            Err(err) => cx.throw_error(err.to_string())
         }
     })?;
     
+    cx.export_function("stream", |mut cx: FunctionContext| -> JsResult<JsUndefined> {
+        let name = cx.argument::<JsString>(0)?.value(&mut cx);
+        let data = cx.argument::<JsObject>(1)?.upcast::<JsValue>();
+        let output = cx.argument::<JsValue>(2)?;
+        let mut chunk_size = 16384;
+        let opts = cx.argument_opt(3);
+        let stringify = stringify_option(&mut cx, opts)?;
+        if let Some(opts) = opts {
+            if let Ok(opts) = opts.downcast::<JsObject, _>(&mut cx) {
+                let size = opts.get(&mut cx, "chunkSize")?;
+                if let Ok(size) = size.downcast::<JsNumber, _>(&mut cx) {
+                    chunk_size = size.value(&mut cx).max(0.0) as usize;
+                }
+            }
+        }
+        let compiler = COMPILER.read().unwrap();
+        let helpers = JS_HELPERS.lock().unwrap();
+        let (res, threw) = {
+            let mut source = NodeSource::with_output(&mut cx, &helpers, output);
+            source.stringify = stringify;
+            let data = source.raw(data);
+            (compiler.stream(&name, data, &mut source, chunk_size), source.threw)
+        };
+        match res {
+            Ok(()) => Ok(cx.undefined()),
+            // Rethrow the exception of the JS function
+            Err(_) if threw => Err(Throw),
+            Err(err) => cx.throw_error(err.to_string())
+        }
+    })?;
+
+    cx.export_function("renderAsync", |mut cx: FunctionContext| -> JsResult<JsValue> {
+        let name = cx.argument::<JsString>(0)?;
+        let data = cx.argument::<JsObject>(1)?;
+        let opts = cx.argument_opt(2).unwrap_or_else(|| cx.undefined().upcast());
+        render_async_promise(&mut cx, name, data, opts)
+    })?;
+
+    cx.export_function("renderInBackground", |mut cx: FunctionContext| -> JsResult<JsValue> {
+        let name = cx.argument::<JsString>(0)?;
+        let data = cx.argument::<JsObject>(1)?;
+        let opts = cx.argument_opt(2).unwrap_or_else(|| cx.undefined().upcast());
+        let stringify = stringify_option(&mut cx, Some(opts))?;
+        // Data with functions can't be copied, the template is rendered on the main thread then
+        let snapshot = match node::snapshot(&mut cx, data.upcast(), stringify.is_some(), 0)? {
+            Some(snapshot) => snapshot,
+            None => return render_async_promise(&mut cx, name, data, opts)
+        };
+        let holder = cx.empty_object();
+        let executor = JsFunction::new(&mut cx, promise_executor)?;
+        let executor = bind(&mut cx, executor, vec![holder.upcast()])?;
+        let promise = cx.global().get(&mut cx, "Promise")?.downcast_or_throw::<JsFunction, _>(&mut cx)?;
+        let promise = promise.construct(&mut cx, vec![executor])?;
+        // The options can be any value, so they're kept in the rooted holder
+        holder.set(&mut cx, "options", opts)?;
+        let holder = holder.root(&mut cx);
+        let data = data.root(&mut cx);
+        let name = name.value(&mut cx);
+        let channel = cx.channel();
+        POOL.execute(move || {
+            let res = COMPILER.read().unwrap().render_raw(&name, snapshot);
+            channel.send(move |mut cx| {
+                let holder = holder.into_inner(&mut cx);
+                let (name, data) = (cx.string(name), data.into_inner(&mut cx));
+                let opts = holder.get(&mut cx, "options")?;
+                let resolve = holder.get(&mut cx, "resolve")?.downcast_or_throw::<JsFunction, _>(&mut cx)?;
+                // The template needs something only the main thread has, like a JS helper. If it's really
+                // broken, the render on the main thread rejects the promise
+                let res = match res {
+                    Ok(res) => cx.string(res).upcast::<JsValue>(),
+                    Err(_) => render_async_promise(&mut cx, name, data, opts)?
+                };
+                let undefined = cx.undefined();
+                resolve.call(&mut cx, undefined, vec![res])?;
+                Ok(())
+            });
+        });
+        Ok(promise.upcast())
+    })?;
+
     cx.export_function("addHelper", |mut cx: FunctionContext| -> JsResult<JsUndefined> {
         let name = cx.argument::<JsString>(0)?.value(&mut cx);
         let value = cx.argument::<JsFunction>(1)?.root(&mut cx);
-        COMPILER.lock().unwrap().add_helper(name, value);
+        COMPILER.write().unwrap().register_helper(&name, HostHelper);
+        JS_HELPERS.lock().unwrap().insert(name, value);
         Ok(cx.undefined())
     })?;
 
     cx.export_function("removeHelper", |mut cx: FunctionContext| -> JsResult<JsUndefined> {
         let name = cx.argument::<JsString>(0)?.value(&mut cx);
-        COMPILER.lock().unwrap().helpers.remove(&name);
+        COMPILER.write().unwrap().remove_helper(&name);
+        JS_HELPERS.lock().unwrap().remove(&name);
         Ok(cx.undefined())
     })?;
 
     Ok(())
 }
+
+// Starts rendering a template asynchronously on the main thread, see `render_async`
+fn render_async_promise<'a, C: Context<'a>>(cx: &mut C, name: Handle<'a, JsString>, data: Handle<'a, JsObject>, opts: Handle<'a, JsValue>) -> JsResult<'a, JsValue> {
+    let state = cx.empty_object();
+    state.set(cx, "name", name)?;
+    state.set(cx, "data", data)?;
+    state.set(cx, "options", opts)?;
+    let calls = cx.empty_array();
+    state.set(cx, "calls", calls)?;
+    let renders = cx.number(0);
+    state.set(cx, "renders", renders)?;
+    // Every render runs in a promise callback, so errors reject the returned promise
+    let render = JsFunction::new(cx, render_async)?;
+    let render = bind(cx, render, vec![state.upcast()])?;
+    let promise = cx.global().get(cx, "Promise")?.downcast_or_throw::<JsFunction, _>(cx)?;
+    let resolve = promise.get(cx, "resolve")?.downcast_or_throw::<JsFunction, _>(cx)?;
+    let resolved = resolve.call(cx, promise, Vec::<Handle<JsValue>>::new())?;
+    then(cx, resolved, render)
+}
+
+// Keeps the resolve function of a promise in the object it's bound to
+fn promise_executor(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let holder = cx.argument::<JsObject>(0)?;
+    let resolve = cx.argument::<JsFunction>(1)?;
+    holder.set(&mut cx, "resolve", resolve)?;
+    Ok(cx.undefined())
+}
+
+// Renders the template of a `renderAsync` call. If functions returned promises, the template is
+// rendered again once all of them are resolved, with their values
+fn render_async(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let state = cx.argument::<JsObject>(0)?;
+    let table = state.get(&mut cx, "calls")?.downcast_or_throw::<JsArray, _>(&mut cx)?;
+    // The indices of the calls and the values of their promises, when called by Promise.all
+    if let Some(values) = cx.argument_opt(2) {
+        let indices = cx.argument::<JsArray>(1)?.to_vec(&mut cx)?;
+        let values = values.downcast_or_throw::<JsArray, _>(&mut cx)?.to_vec(&mut cx)?;
+        for (ind, value) in indices.into_iter().zip(values) {
+            let ind = ind.downcast_or_throw::<JsNumber, _>(&mut cx)?.value(&mut cx) as u32;
+            let entry = table.get(&mut cx, ind)?.downcast_or_throw::<JsObject, _>(&mut cx)?;
+            entry.set(&mut cx, "value", value)?;
+            let done = cx.boolean(true);
+            entry.set(&mut cx, "done", done)?;
+        }
+    }
+    let renders = state.get(&mut cx, "renders")?.downcast_or_throw::<JsNumber, _>(&mut cx)?.value(&mut cx) + 1.0;
+    if renders > MAX_ASYNC_RENDERS {
+        return cx.throw_error(format!("The template was rendered {} times, but new promises kept being returned. Are the arguments of a function different in every render?", MAX_ASYNC_RENDERS));
+    }
+    let renders = cx.number(renders);
+    state.set(&mut cx, "renders", renders)?;
+    let name = state.get(&mut cx, "name")?.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+    let data = state.get(&mut cx, "data")?;
+    let opts = state.get(&mut cx, "options")?;
+    let stringify = stringify_option(&mut cx, Some(opts))?;
+    let (res, threw, pending) = {
+        let compiler = COMPILER.read().unwrap();
+        let helpers = JS_HELPERS.lock().unwrap();
+        let calls = AsyncCalls::new(&mut cx, table)?;
+        let mut source = NodeSource::with_calls(&mut cx, &helpers, calls);
+        source.stringify = stringify;
+        let data = source.raw(data);
+        let res = compiler.compile(&name, data, &mut source);
+        (res, source.threw, source.calls.take().map(|calls| calls.pending).unwrap_or_default())
+    };
+    if threw {
+        return Err(Throw);
+    }
+    // Errors can be caused by the missing values, so they only count once nothing is pending
+    if !pending.is_empty() {
+        let indices = JsArray::new(&mut cx, pending.len() as u32);
+        let promises = JsArray::new(&mut cx, pending.len() as u32);
+        for (pending_ind, (ind, promise)) in pending.into_iter().enumerate() {
+            let ind = cx.number(ind);
+            indices.set(&mut cx, pending_ind as u32, ind)?;
+            promises.set(&mut cx, pending_ind as u32, promise)?;
+        }
+        let promise = cx.global().get(&mut cx, "Promise")?.downcast_or_throw::<JsFunction, _>(&mut cx)?;
+        let all = promise.get(&mut cx, "all")?.downcast_or_throw::<JsFunction, _>(&mut cx)?;
+        let all = all.call(&mut cx, promise, vec![promises])?;
+        let render = JsFunction::new(&mut cx, render_async)?;
+        let render = bind(&mut cx, render, vec![state.upcast(), indices.upcast()])?;
+        return then(&mut cx, all, render);
+    }
+    match res {
+        Ok(res) => Ok(cx.string(res).upcast()),
+        Err(err) => cx.throw_error(err.to_string())
+    }
+}
+
+fn bind<'a, C: Context<'a>>(cx: &mut C, func: Handle<'a, JsFunction>, args: Vec<Handle<'a, JsValue>>) -> JsResult<'a, JsFunction> {
+    let bind = func.get(cx, "bind")?.downcast_or_throw::<JsFunction, _>(cx)?;
+    let mut bind_args = vec![cx.null().upcast::<JsValue>()];
+    bind_args.extend(args);
+    bind.call(cx, func, bind_args)?.downcast_or_throw::<JsFunction, _>(cx)
+}
+
+fn then<'a, C: Context<'a>>(cx: &mut C, promise: Handle<'a, JsValue>, callback: Handle<'a, JsFunction>) -> JsResult<'a, JsValue> {
+    let promise = promise.downcast_or_throw::<JsObject, _>(cx)?;
+    let then = promise.get(cx, "then")?.downcast_or_throw::<JsFunction, _>(cx)?;
+    then.call(cx, promise, vec![callback])
+}
+
+// The `stringify` option of the render functions
+fn stringify_option<'a, C: Context<'a>>(cx: &mut C, opts: Option<Handle<'a, JsValue>>) -> NeonResult<Option<Stringify>> {
+    let opts = match opts.and_then(|opts| opts.downcast::<JsObject, _>(cx).ok()) {
+        Some(opts) => opts,
+        None => return Ok(None)
+    };
+    let mode = opts.get(cx, "stringify")?;
+    if mode.is_a::<JsUndefined, _>(cx) {
+        return Ok(None);
+    }
+    match mode.downcast::<JsString, _>(cx).map(|mode| mode.value(cx)).as_deref() {
+        Ok("toString") => Ok(Some(Stringify::ToString)),
+        Ok("toJSON") => Ok(Some(Stringify::ToJson)),
+        _ => cx.throw_error("The stringify option must be \"toString\" or \"toJSON\"")
+    }
+}
+
+fn parser_options(cx: &mut FunctionContext, index: i32) -> NeonResult<ParserOptions> {
+    let mut options = ParserOptions::default();
+    if let Some(opts) = cx.argument_opt(index) {
+        if let Ok(opts) = opts.downcast::<JsObject, _>(cx) {
+            let bigint = opts.get(cx, "bigintLiterals")?;
+            options.bigint_integers = bigint.downcast::<JsBoolean, _>(cx).map(|b| b.value(cx)).unwrap_or(false);
+        }
+    }
+    Ok(options)
+}
+
+// Converts all positions from byte offsets to UTF-16 offsets, which is how JS indexes strings
+struct Utf16Positions {
+    offsets: Vec<usize>
+}
+
+impl Utf16Positions {
+
+    fn new(source: &str) -> Self {
+        let mut offsets = vec![0; source.len() + 1];
+        let mut utf16_ind = 0;
+        for (ind, ch) in source.char_indices() {
+            offsets[ind] = utf16_ind;
+            utf16_ind += ch.len_utf16();
+        }
+        offsets[source.len()] = utf16_ind;
+        Self { offsets }
+    }
+
+    fn convert(&self, pos: &mut Range<usize>) {
+        *pos = self.offsets[pos.start]..self.offsets[pos.end];
+    }
+
+}
+
+impl VisitorMut for Utf16Positions {
+
+    fn visit_subtext_mut(&mut self, text: &mut ast::SubText) {
+        self.convert(&mut text.pos);
+        visit::walk_subtext_mut(self, text);
+    }
+
+    fn visit_template_mut(&mut self, template: &mut ast::Template) {
+        self.convert(&mut template.pos);
+        visit::walk_template_mut(self, template);
+    }
+
+}
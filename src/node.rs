@@ -0,0 +1,499 @@
+use neon::types::{JsString, JsArray, JsBoolean, JsUndefined, JsNull, JsNumber, JsValue, JsObject, JsFunction};
+use neon::handle::{Handle, Root};
+use neon::context::{Context, FunctionContext};
+use neon::result::{NeonResult, Throw};
+use neon::object::Object;
+use finch_core::error::{FinchError, FinchResult};
+use finch_core::value::{RawValue, HostRef, DataSource};
+use std::sync::Arc;
+use std::collections::HashMap;
+
+/// The calls made while rendering with `renderAsync`. The template is rendered again whenever
+/// the promises returned by the calls of a render are resolved, and the calls are matched up
+/// with the ones of earlier renders by their function and their arguments.
+pub struct AsyncCalls<'cx> {
+    /// `{ key, hosts, value, done }` for every call made so far. `key` holds the arguments which
+    /// live in rust, `hosts` the function and the arguments owned by JS. `value` is the returned
+    /// value, or the promise if it isn't `done`.
+    table: Handle<'cx, JsArray>,
+    /// The indices of the calls in the table by their `key`
+    index: HashMap<String, Vec<u32>>,
+    /// The calls of this render which returned promises, and the promises
+    pub pending: Vec<(u32, Handle<'cx, JsValue>)>
+}
+
+impl<'cx> AsyncCalls<'cx> {
+
+    pub fn new<C: Context<'cx>>(cx: &mut C, table: Handle<'cx, JsArray>) -> NeonResult<Self> {
+        let mut index: HashMap<String, Vec<u32>> = HashMap::new();
+        for ind in 0..table.len(cx) {
+            let entry = table.get(cx, ind)?.downcast_or_throw::<JsObject, _>(cx)?;
+            let key = entry.get(cx, "key")?.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+            index.entry(key).or_default().push(ind);
+        }
+        Ok(Self { table, index, pending: vec![] })
+    }
+
+}
+
+/// How objects are converted to text, set with the `stringify` option of the render functions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stringify {
+    /// Calls `String(value)`, which uses the object's own `toString`
+    ToString,
+    /// Calls the object's `toJSON` method if it has one, like `Date`s do, and `String` on the result
+    ToJson
+}
+
+/// Gives the renderer access to the JS values passed to `compile`. Objects, arrays
+/// and functions stay in JS, everything else is copied into a [RawValue].
+pub struct NodeSource<'a, 'cx> {
+    pub cx: &'a mut FunctionContext<'cx>,
+    handles: Vec<Handle<'cx, JsValue>>,
+    helpers: &'a HashMap<String, Root<JsFunction>>,
+    // Where streamed output goes, a function or an object with a write method
+    output: Option<Handle<'cx, JsValue>>,
+    /// Set when a JS function threw, the exception is still pending then
+    pub threw: bool,
+    pub calls: Option<AsyncCalls<'cx>>,
+    /// Objects are written as `[object Object]` if it's not set
+    pub stringify: Option<Stringify>
+}
+
+impl<'a, 'cx> NodeSource<'a, 'cx> {
+
+    pub fn new(cx: &'a mut FunctionContext<'cx>, helpers: &'a HashMap<String, Root<JsFunction>>) -> Self {
+        Self { cx, handles: vec![], helpers, output: None, threw: false, calls: None, stringify: None }
+    }
+
+    pub fn with_output(cx: &'a mut FunctionContext<'cx>, helpers: &'a HashMap<String, Root<JsFunction>>, output: Handle<'cx, JsValue>) -> Self {
+        Self { cx, handles: vec![], helpers, output: Some(output), threw: false, calls: None, stringify: None }
+    }
+
+    pub fn with_calls(cx: &'a mut FunctionContext<'cx>, helpers: &'a HashMap<String, Root<JsFunction>>, calls: AsyncCalls<'cx>) -> Self {
+        Self { cx, handles: vec![], helpers, output: None, threw: false, calls: Some(calls), stringify: None }
+    }
+
+    // Calls a JS function. When rendering asynchronously, the result of the same call made earlier is
+    // reused, and None is returned for promises until they're resolved. `key` identifies the call, with the function
+    fn call_js(&mut self, func: Handle<'cx, JsFunction>, args: Vec<Handle<'cx, JsValue>>, key: &[RawValue]) -> FinchResult<Option<Handle<'cx, JsValue>>> {
+        if self.calls.is_none() {
+            return self.call_sync(func, args).map(Some);
+        }
+        let (mut key_str, mut hosts) = (String::new(), vec![func.upcast::<JsValue>()]);
+        self.call_key(key, &mut key_str, &mut hosts);
+        if let Some((value, done)) = self.earlier_call(&key_str, &hosts).map_err(external)? {
+            return Ok(if done { Some(value) } else { None });
+        }
+        let value = self.call_sync(func, args)?;
+        let done = !self.is_thenable(value)?;
+        let entry = self.cx.empty_object();
+        let key_js = self.cx.string(&key_str);
+        let hosts_js = JsArray::new(self.cx, hosts.len() as u32);
+        for (host_ind, host) in hosts.into_iter().enumerate() {
+            hosts_js.set(self.cx, host_ind as u32, host).map_err(external)?;
+        }
+        let done_js = self.cx.boolean(done);
+        entry.set(self.cx, "key", key_js).map_err(external)?;
+        entry.set(self.cx, "hosts", hosts_js).map_err(external)?;
+        entry.set(self.cx, "value", value).map_err(external)?;
+        entry.set(self.cx, "done", done_js).map_err(external)?;
+        let calls = self.calls.as_mut().unwrap();
+        let ind = calls.table.len(self.cx);
+        calls.table.set(self.cx, ind, entry).map_err(external)?;
+        calls.index.entry(key_str).or_default().push(ind);
+        if done {
+            Ok(Some(value))
+        } else {
+            calls.pending.push((ind, value));
+            Ok(None)
+        }
+    }
+
+    fn call_sync(&mut self, func: Handle<'cx, JsFunction>, args: Vec<Handle<'cx, JsValue>>) -> FinchResult<Handle<'cx, JsValue>> {
+        let undefined = self.cx.undefined();
+        func.call(self.cx, undefined, args).map_err(|er| {
+            self.threw = true;
+            external(er)
+        })
+    }
+
+    // Values which live in rust are copied into new JS values for every render, so they're compared
+    // by their contents. Objects and functions owned by JS are compared by identity, they're collected in `hosts`
+    fn call_key(&self, vals: &[RawValue], key: &mut String, hosts: &mut Vec<Handle<'cx, JsValue>>) {
+        for val in vals {
+            match val {
+                RawValue::HostObject(obj) | RawValue::HostArray(obj) | RawValue::Function(obj) => {
+                    hosts.push(self.handles[obj.0]);
+                    key.push_str("Host;");
+                },
+                RawValue::Vec(items) => {
+                    key.push('[');
+                    self.call_key(items, key, hosts);
+                    key.push_str("];");
+                },
+                RawValue::Object(map) => {
+                    let mut entries = map.iter().collect::<Vec<(&String, &RawValue)>>();
+                    entries.sort_by(|a, b| a.0.cmp(b.0));
+                    key.push('{');
+                    for (name, val) in entries {
+                        key.push_str(&format!("{:?}:", name));
+                        self.call_key(std::slice::from_ref(val), key, hosts);
+                    }
+                    key.push_str("};");
+                },
+                other => key.push_str(&format!("{:?};", other))
+            }
+        }
+    }
+
+    // The value of an earlier call with the same key and hosts, and whether it's done. Calls which are
+    // still pending were made in this render, their promises are already waited for
+    fn earlier_call(&mut self, key: &str, hosts: &[Handle<'cx, JsValue>]) -> NeonResult<Option<(Handle<'cx, JsValue>, bool)>> {
+        let calls = self.calls.as_ref().unwrap();
+        let (table, candidates) = match calls.index.get(key) {
+            Some(candidates) => (calls.table, candidates.clone()),
+            None => return Ok(None)
+        };
+        for ind in candidates {
+            let entry = table.get(self.cx, ind)?.downcast_or_throw::<JsObject, _>(self.cx)?;
+            let earlier_hosts = entry.get(self.cx, "hosts")?.downcast_or_throw::<JsArray, _>(self.cx)?.to_vec(self.cx)?;
+            let same = earlier_hosts.len() == hosts.len() &&
+                earlier_hosts.iter().zip(hosts).all(|(earlier, host)| earlier.strict_equals(self.cx, *host));
+            if same {
+                let done = entry.get(self.cx, "done")?.downcast_or_throw::<JsBoolean, _>(self.cx)?.value(self.cx);
+                return Ok(Some((entry.get(self.cx, "value")?, done)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn is_thenable(&mut self, value: Handle<'cx, JsValue>) -> FinchResult<bool> {
+        match value.downcast::<JsObject, _>(self.cx) {
+            Ok(obj) => {
+                let then = obj.get(self.cx, "then").map_err(|er| {
+                    self.threw = true;
+                    external(er)
+                })?;
+                Ok(then.is_a::<JsFunction, _>(self.cx))
+            },
+            Err(_) => Ok(false)
+        }
+    }
+
+    // Calls a global function like `String`
+    fn call_global(&mut self, name: &str, this: Handle<'cx, JsValue>, args: Vec<Handle<'cx, JsValue>>) -> FinchResult<Handle<'cx, JsValue>> {
+        let func = self.cx.global().get(self.cx, name)
+            .and_then(|func| func.downcast_or_throw::<JsFunction, _>(self.cx))
+            .and_then(|func| func.call(self.cx, this, args));
+        func.map_err(|er| {
+            self.threw = true;
+            external(er)
+        })
+    }
+
+    fn host_ref(&mut self, handle: Handle<'cx, JsValue>) -> HostRef {
+        self.handles.push(handle);
+        HostRef(self.handles.len() - 1)
+    }
+
+    pub fn raw(&mut self, handle: Handle<'cx, JsValue>) -> RawValue {
+        let cx = &mut *self.cx;
+        if let Ok(str_handle) = handle.downcast::<JsString, _>(cx) {
+            RawValue::String(str_handle.value(cx))
+        } else if let Ok(num_handle) = handle.downcast::<JsNumber, _>(cx) {
+            RawValue::Number(num_handle.value(cx))
+        } else if let Ok(bool_handle) = handle.downcast::<JsBoolean, _>(cx) {
+            RawValue::Boolean(bool_handle.value(cx))
+        } else if handle.is_a::<JsNull, _>(cx) {
+            RawValue::Null
+        } else if handle.is_a::<JsUndefined, _>(cx) {
+            RawValue::Undefined
+        } else if handle.is_a::<JsArray, _>(cx) {
+            RawValue::HostArray(self.host_ref(handle))
+        } else if handle.is_a::<JsFunction, _>(cx) {
+            RawValue::Function(self.host_ref(handle))
+        } else if handle.is_a::<JsObject, _>(cx) {
+            RawValue::HostObject(self.host_ref(handle))
+        } else {
+            bigint(cx, handle).map(RawValue::BigInt).unwrap_or(RawValue::Undefined)
+        }
+    }
+
+    pub fn js(&mut self, val: &RawValue) -> Handle<'cx, JsValue> {
+        match val {
+            RawValue::String(val) => self.cx.string(val).upcast::<JsValue>(),
+            RawValue::Number(num) => self.cx.number(*num).upcast::<JsValue>(),
+            RawValue::BigInt(num) => {
+                // Neon has no BigInt type, so the global BigInt function creates the value
+                let cx = &mut *self.cx;
+                let num_str = cx.string(num.to_string()).upcast::<JsValue>();
+                let undefined = cx.undefined();
+                cx.global().get(cx, "BigInt")
+                    .and_then(|func| func.downcast_or_throw::<JsFunction, _>(cx))
+                    .and_then(|func| func.call(cx, undefined, vec![num_str]))
+                    .unwrap_or_else(|_| cx.number(*num as f64).upcast::<JsValue>())
+            },
+            RawValue::Boolean(b) => self.cx.boolean(*b).upcast::<JsValue>(),
+            RawValue::Undefined => self.cx.undefined().upcast::<JsValue>(),
+            RawValue::Null => self.cx.null().upcast::<JsValue>(),
+            RawValue::Vec(v) => {
+                let arr = JsArray::new(self.cx, v.len() as u32);
+                for (ind, val) in v.iter().enumerate() {
+                    let js_val = self.js(val);
+                    arr.set(self.cx, ind as u32, js_val).unwrap();
+                };
+                arr.upcast::<JsValue>()
+            },
+            RawValue::Object(map) => {
+                let obj = self.cx.empty_object();
+                for (key, val) in map.iter() {
+                    let js_val = self.js(val);
+                    obj.set(self.cx, key.as_str(), js_val).unwrap();
+                }
+                obj.upcast::<JsValue>()
+            },
+            RawValue::HostObject(obj) | RawValue::HostArray(obj) | RawValue::Function(obj) => self.handles[obj.0]
+        }
+    }
+
+}
+
+impl<'a, 'cx> DataSource for NodeSource<'a, 'cx> {
+
+    fn prop(&mut self, obj: HostRef, key: &str) -> FinchResult<RawValue> {
+        let obj = self.handles[obj.0].downcast::<JsObject, _>(self.cx).map_err(|_| FinchError::ExpectedObject)?;
+        let val = obj.get(self.cx, key).map_err(|_| FinchError::PropNotExist(key.to_string()))?;
+        Ok(self.raw(val))
+    }
+
+    fn len(&mut self, arr: HostRef) -> FinchResult<usize> {
+        let arr = self.handles[arr.0].downcast::<JsArray, _>(self.cx).map_err(|_| FinchError::ExpectedObject)?;
+        Ok(arr.len(self.cx) as usize)
+    }
+
+    fn item(&mut self, arr: HostRef, ind: usize) -> FinchResult<RawValue> {
+        let arr = self.handles[arr.0].downcast::<JsArray, _>(self.cx).map_err(|_| FinchError::ExpectedObject)?;
+        let val = arr.get(self.cx, ind as u32).map_err(|_| FinchError::PropNotExist(ind.to_string()))?;
+        Ok(self.raw(val))
+    }
+
+    fn to_string(&mut self, obj: HostRef) -> FinchResult<Option<String>> {
+        let handle = self.handles[obj.0];
+        let undefined = self.cx.undefined().upcast::<JsValue>();
+        let res = match self.stringify {
+            None if handle.is_a::<JsFunction, _>(self.cx) => {
+                // Function.prototype.toString, which a function can't replace
+                let to_string = self.cx.global().get(self.cx, "Function")
+                    .and_then(|func| func.downcast_or_throw::<JsFunction, _>(self.cx))
+                    .and_then(|func| func.get(self.cx, "prototype"))
+                    // Function.prototype is a function too
+                    .and_then(|proto| proto.downcast_or_throw::<JsFunction, _>(self.cx))
+                    .and_then(|proto| proto.get(self.cx, "toString"))
+                    .and_then(|func| func.downcast_or_throw::<JsFunction, _>(self.cx))
+                    .and_then(|func| func.call(self.cx, handle, Vec::<Handle<JsValue>>::new()));
+                to_string.map_err(|er| {
+                    self.threw = true;
+                    external(er)
+                })?
+            },
+            None => return Ok(None),
+            Some(Stringify::ToJson) => {
+                let to_json = match handle.downcast::<JsObject, _>(self.cx) {
+                    Ok(obj) => obj.get(self.cx, "toJSON").map_err(|er| {
+                        self.threw = true;
+                        external(er)
+                    })?,
+                    Err(_) => undefined
+                };
+                match to_json.downcast::<JsFunction, _>(self.cx) {
+                    Ok(to_json) => {
+                        let value = to_json.call(self.cx, handle, Vec::<Handle<JsValue>>::new()).map_err(|er| {
+                            self.threw = true;
+                            external(er)
+                        })?;
+                        self.call_global("String", undefined, vec![value])?
+                    },
+                    Err(_) => self.call_global("String", undefined, vec![handle])?
+                }
+            },
+            Some(Stringify::ToString) => self.call_global("String", undefined, vec![handle])?
+        };
+        let res = res.downcast::<JsString, _>(self.cx).map_err(|_| FinchError::None)?;
+        Ok(Some(res.value(self.cx)))
+    }
+
+    fn same(&mut self, left: HostRef, right: HostRef) -> FinchResult<bool> {
+        Ok(self.handles[left.0].strict_equals(self.cx, self.handles[right.0]))
+    }
+
+    fn keys(&mut self, obj: HostRef) -> FinchResult<Vec<String>> {
+        let obj = self.handles[obj.0].downcast::<JsObject, _>(self.cx).map_err(|_| FinchError::ExpectedObject)?;
+        let names = obj.get_own_property_names(self.cx).and_then(|names| names.to_vec(self.cx)).map_err(|er| {
+            self.threw = true;
+            external(er)
+        })?;
+        let mut keys = Vec::with_capacity(names.len());
+        for name in names {
+            if let Ok(name) = name.downcast::<JsString, _>(self.cx) {
+                keys.push(name.value(self.cx));
+            }
+        }
+        Ok(keys)
+    }
+
+    fn call(&mut self, func_ref: HostRef, args: Vec<RawValue>) -> FinchResult<RawValue> {
+        let func = self.handles[func_ref.0].downcast::<JsFunction, _>(self.cx).map_err(|_| FinchError::NotCallable)?;
+        let js_args = args.iter().map(|arg| self.js(arg)).collect::<Vec<Handle<JsValue>>>();
+        match self.call_js(func, js_args, &args).map_err(|_| FinchError::ErrInFunction)? {
+            Some(res) => Ok(self.raw(res)),
+            None => Ok(RawValue::Undefined)
+        }
+    }
+
+    fn call_helper(&mut self, name: &str, params: Vec<RawValue>, hash: HashMap<String, RawValue>, body: Option<String>) -> FinchResult<String> {
+        let func = self.helpers.get(name).ok_or_else(|| FinchError::HelperNotFound(name.to_string()))?.to_inner(self.cx);
+        let args_arr = JsArray::new(self.cx, params.len() as u32);
+        for (ind, param) in params.iter().enumerate() {
+            let param_js = self.js(param);
+            args_arr.set(self.cx, ind as u32, param_js).map_err(|er| FinchError::External(er.to_string()))?;
+        }
+        let body_val = body.clone().map(RawValue::String).unwrap_or(RawValue::Undefined);
+        let body = if let Some(b) = body {
+            self.cx.string(b).upcast::<JsValue>()
+        } else { self.cx.undefined().upcast::<JsValue>() };
+        let mut key = vec![RawValue::from(name), body_val];
+        let mut hash_entries = hash.iter().collect::<Vec<(&String, &RawValue)>>();
+        hash_entries.sort_by(|a, b| a.0.cmp(b.0));
+        for (key_name, value) in hash_entries {
+            key.push(RawValue::from(key_name.as_str()));
+            key.push(value.clone());
+        }
+        key.extend(params.iter().cloned());
+        let hash = self.js(&RawValue::from(hash));
+        let res = match self.call_js(func, vec![args_arr.upcast::<JsValue>(), body, hash], &key)? {
+            Some(res) => res,
+            // Pending helpers render nothing until they're resolved
+            None => return Ok(String::new())
+        };
+        let res = self.raw(res);
+        res.stringify(self)
+    }
+
+    fn eval(&mut self, code: &str, data: &RawValue) -> FinchResult<RawValue> {
+        let val = self.cx.string(code);
+        let param_name = self.cx.string("data");
+        let func = self.cx.global().get(self.cx, "Function").map_err(|_| FinchError::None)?.downcast::<JsFunction, _>(self.cx).map_err(|_| FinchError::None)?;
+        let res = func.construct(self.cx, vec![param_name, val]).map_err(|_| FinchError::None)?.downcast::<JsFunction, _>(self.cx).map_err(|_| FinchError::None)?;
+        let data = self.js(data);
+        let undefined = self.cx.undefined();
+        let result = res.call(self.cx, undefined, vec![data]).map_err(|er| {
+            self.threw = true;
+            FinchError::External(er.to_string())
+        })?;
+        Ok(self.raw(result))
+    }
+
+    // A function which threw leaves its exception pending, nothing can be called until it's thrown
+    fn pending(&self) -> bool {
+        !self.threw && self.calls.as_ref().is_some_and(|calls| !calls.pending.is_empty())
+    }
+
+    fn write(&mut self, chunk: &str) -> FinchResult<()> {
+        let output = self.output.ok_or_else(|| FinchError::Custom(String::from("This data source cannot stream output")))?;
+        let chunk = self.cx.string(chunk).upcast::<JsValue>();
+        let (func, this) = if let Ok(func) = output.downcast::<JsFunction, _>(self.cx) {
+            (func, self.cx.undefined().upcast::<JsValue>())
+        } else {
+            let obj = output.downcast::<JsObject, _>(self.cx).map_err(|_| FinchError::NotCallable)?;
+            let write = obj.get(self.cx, "write").map_err(|er| {
+                self.threw = true;
+                FinchError::External(er.to_string())
+            })?;
+            (write.downcast::<JsFunction, _>(self.cx).map_err(|_| FinchError::NotCallable)?, output)
+        };
+        func.call(self.cx, this, vec![chunk]).map_err(|er| {
+            self.threw = true;
+            FinchError::External(er.to_string())
+        })?;
+        Ok(())
+    }
+
+}
+
+// How deeply nested data can be to be copied by `snapshot`, deeper data is probably circular
+const MAX_SNAPSHOT_DEPTH: usize = 64;
+
+/// Copies JS data into rust, so it can be rendered without JS. `None` if it contains values
+/// which can't be copied, like functions. With `own_strings`, objects which aren't plain
+/// objects can't be copied either, since they're converted to text with their own methods.
+pub fn snapshot<'a, C: Context<'a>>(cx: &mut C, handle: Handle<'a, JsValue>, own_strings: bool, depth: usize) -> NeonResult<Option<RawValue>> {
+    if depth > MAX_SNAPSHOT_DEPTH {
+        return Ok(None);
+    }
+    Ok(Some(if let Ok(str_handle) = handle.downcast::<JsString, _>(cx) {
+        RawValue::String(str_handle.value(cx))
+    } else if let Ok(num_handle) = handle.downcast::<JsNumber, _>(cx) {
+        RawValue::Number(num_handle.value(cx))
+    } else if let Ok(bool_handle) = handle.downcast::<JsBoolean, _>(cx) {
+        RawValue::Boolean(bool_handle.value(cx))
+    } else if handle.is_a::<JsNull, _>(cx) {
+        RawValue::Null
+    } else if handle.is_a::<JsUndefined, _>(cx) {
+        RawValue::Undefined
+    } else if let Ok(arr_handle) = handle.downcast::<JsArray, _>(cx) {
+        let mut items = vec![];
+        for item in arr_handle.to_vec(cx)? {
+            match snapshot(cx, item, own_strings, depth + 1)? {
+                Some(item) => items.push(item),
+                None => return Ok(None)
+            }
+        }
+        RawValue::Vec(Arc::new(items))
+    } else if handle.is_a::<JsFunction, _>(cx) {
+        return Ok(None);
+    } else if let Ok(obj_handle) = handle.downcast::<JsObject, _>(cx) {
+        if own_strings && !is_plain(cx, obj_handle)? {
+            return Ok(None);
+        }
+        let mut map = HashMap::new();
+        for key in obj_handle.get_own_property_names(cx)?.to_vec(cx)? {
+            let key = match key.downcast::<JsString, _>(cx) {
+                Ok(key) => key.value(cx),
+                Err(_) => return Ok(None)
+            };
+            let val = obj_handle.get(cx, key.as_str())?;
+            match snapshot(cx, val, own_strings, depth + 1)? {
+                Some(val) => map.insert(key, val),
+                None => return Ok(None)
+            };
+        }
+        RawValue::Object(Arc::new(map))
+    } else if let Some(num) = bigint(cx, handle) {
+        RawValue::BigInt(num)
+    } else {
+        return Ok(None);
+    }))
+}
+
+// The value of a BigInt, neon has no type for them, so it's read through `String`. `None` for
+// symbols, and BigInts which don't fit in an i128
+fn bigint<'a, C: Context<'a>>(cx: &mut C, handle: Handle<'a, JsValue>) -> Option<i128> {
+    let string = cx.global().get(cx, "String").ok()?.downcast::<JsFunction, _>(cx).ok()?;
+    let undefined = cx.undefined();
+    let text = string.call(cx, undefined, vec![handle]).ok()?.downcast::<JsString, _>(cx).ok()?;
+    text.value(cx).parse::<i128>().ok()
+}
+
+// Whether the prototype of an object is `Object.prototype` or null
+fn is_plain<'a, C: Context<'a>>(cx: &mut C, obj: Handle<'a, JsObject>) -> NeonResult<bool> {
+    let object = cx.global().get(cx, "Object")?.downcast_or_throw::<JsFunction, _>(cx)?;
+    let get_prototype = object.get(cx, "getPrototypeOf")?.downcast_or_throw::<JsFunction, _>(cx)?;
+    let proto = get_prototype.call(cx, object, vec![obj])?;
+    let object_proto = object.get(cx, "prototype")?;
+    Ok(proto.is_a::<JsNull, _>(cx) || proto.strict_equals(cx, object_proto))
+}
+
+fn external(err: Throw) -> FinchError {
+    FinchError::External(err.to_string())
+}
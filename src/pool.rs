@@ -0,0 +1,36 @@
+//! The threads `renderInBackground` renders templates on. They never touch JS, the results
+//! are sent back to the main thread through a neon `Channel`.
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct Pool {
+    sender: Mutex<mpsc::Sender<Job>>
+}
+
+impl Pool {
+
+    pub fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..size {
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || loop {
+                // The lock is released before the job runs, so the other threads can take jobs
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break
+                }
+            });
+        }
+        Self { sender: Mutex::new(sender) }
+    }
+
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        self.sender.lock().unwrap().send(Box::new(job)).unwrap();
+    }
+
+}
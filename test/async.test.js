@@ -0,0 +1,74 @@
+const test = require("node:test");
+const assert = require("node:assert");
+const Finch = require("../index.node");
+
+const later = (value) => new Promise((resolve) => setTimeout(() => resolve(value), 5));
+
+// Counts the calls of the functions, by name and the arguments `describe` picks
+function counter() {
+    const calls = {};
+    return {
+        calls,
+        wrap: (name, func, describe = (...args) => args) => (...args) => {
+            const key = `${name}(${describe(...args).map(String).join(",")})`;
+            calls[key] = (calls[key] || 0) + 1;
+            return func(...args);
+        }
+    };
+}
+
+test("calls are made once across rounds", async () => {
+    const { calls, wrap } = counter();
+    Finch.addTemplate("async-once", "{{#if ready()}}{{user(1)}}{{/}} {{user(2)}} {{user(1)}}");
+    const res = await Finch.renderAsync("async-once", {
+        ready: wrap("ready", () => later(true)),
+        user: wrap("user", (id) => later(`user${id}`))
+    });
+    assert.strictEqual(res, "user1 user2 user1");
+    assert.deepStrictEqual(calls, { "ready()": 1, "user(2)": 1, "user(1)": 1 });
+});
+
+test("calls which depend on other calls are made in later rounds", async () => {
+    const { calls, wrap } = counter();
+    Finch.addTemplate("async-chain", "{{name(id())}} {{name(2)}}");
+    const res = await Finch.renderAsync("async-chain", {
+        id: wrap("id", () => later(1)),
+        name: wrap("name", (id) => later(`name${id}`))
+    });
+    assert.strictEqual(res, "name1 name2");
+    assert.deepStrictEqual(calls, { "id()": 1, "name(2)": 1, "name(undefined)": 1, "name(1)": 1 });
+});
+
+test("pending values don't stop the round", async () => {
+    const { calls, wrap } = counter();
+    let renders = 0;
+    Finch.addTemplate("async-pending", "{{render}}{{#with user() user}}{{user.name}}{{/}} {{#each items() item}}{{item}}{{/}} {{other()}}");
+    const res = await Finch.renderAsync("async-pending", {
+        get render() {
+            renders++;
+            return "";
+        },
+        user: wrap("user", () => later({ name: "Bob" })),
+        items: wrap("items", () => later([1, 2])),
+        other: wrap("other", () => later("other"))
+    });
+    assert.strictEqual(res, "Bob 12 other");
+    assert.deepStrictEqual(calls, { "user()": 1, "items()": 1, "other()": 1 });
+    // All three promises are found in the first render
+    assert.strictEqual(renders, 2);
+});
+
+test("helpers are matched by their arguments and body", async () => {
+    const { calls, wrap } = counter();
+    Finch.addHelper("asyncUpper", wrap("asyncUpper", (args, body) => later(`${args[0]}${body || ""}`.toUpperCase()), (args, body) => [...args, body]));
+    Finch.addTemplate("async-helper", "{{#asyncUpper \"a\" /}}{{#asyncUpper \"a\"}}b{{/}}{{#asyncUpper \"a\" /}}{{#if x()}}{{#asyncUpper \"c\" /}}{{/}}");
+    const res = await Finch.renderAsync("async-helper", { x: wrap("x", () => later(true)) });
+    assert.strictEqual(res, "AABAC");
+    assert.deepStrictEqual(calls, { "asyncUpper(a,undefined)": 1, "asyncUpper(a,b)": 1, "x()": 1, "asyncUpper(c,undefined)": 1 });
+});
+
+test("errors are reported once nothing is pending", async () => {
+    Finch.addTemplate("async-error", "{{#with user() user}}{{user.name.first}}{{/}}");
+    await assert.rejects(Finch.renderAsync("async-error", { user: () => later({ name: "Bob" }) }), /dot notation/);
+    await assert.rejects(Finch.renderAsync("async-error", { user: () => Promise.reject(new Error("rejected")) }), /rejected/);
+});
@@ -0,0 +1,52 @@
+const test = require("node:test");
+const assert = require("node:assert");
+const Finch = require("../index.node");
+
+test("the data is copied when the render starts", async () => {
+    Finch.addTemplate("background-copy", "{{user.name}} {{#each items i}}{{i.n}},{{/}} {{big}} {{user.name}}");
+    let reads = 0;
+    const user = { get name() { reads++; return "Bob"; } };
+    const data = { user, items: [{ n: 1 }, { n: 2 }], big: 12345678901234567890n };
+    const promise = Finch.renderInBackground("background-copy", data);
+    // Changes made after the call don't reach the render
+    data.items.push({ n: 3 });
+    data.user = { name: "Alice" };
+    assert.strictEqual(await promise, "Bob 1,2, 12345678901234567890 Bob");
+    // The getter was called once, while copying, not whenever the template read it
+    assert.strictEqual(reads, 1);
+});
+
+test("data which can't be copied is rendered on the main thread", async () => {
+    Finch.addTemplate("background-function", "{{greet(name)}}");
+    const calls = [];
+    const res = await Finch.renderInBackground("background-function", {
+        name: "Bob",
+        greet: (name) => { calls.push(name); return `Hi ${name}`; }
+    });
+    assert.strictEqual(res, "Hi Bob");
+    assert.deepStrictEqual(calls, ["Bob"]);
+    // Circular data is too deep to copy
+    Finch.addTemplate("background-circular", "{{self.self.self.n}}");
+    const circular = { n: 7 };
+    circular.self = circular;
+    assert.strictEqual(await Finch.renderInBackground("background-circular", circular), "7");
+});
+
+test("templates which need JS are rendered on the main thread", async () => {
+    Finch.addHelper("backgroundShout", (args) => args[0].toUpperCase());
+    Finch.addTemplate("background-helper", "{{#backgroundShout name /}}!");
+    assert.strictEqual(await Finch.renderInBackground("background-helper", { name: "bob" }), "BOB!");
+    Finch.removeHelper("backgroundShout");
+});
+
+test("errors reject the promise", async () => {
+    Finch.addTemplate("background-error", "{{a.b.c}}");
+    await assert.rejects(Finch.renderInBackground("background-error", {}));
+    await assert.rejects(Finch.renderInBackground("background-missing", {}));
+});
+
+test("templates render in parallel with the same results", async () => {
+    Finch.addTemplate("background-many", "{{#range 1 n i}}{{i * 2}} {{/}}");
+    const results = await Promise.all(Array.from({ length: 32 }, (_, n) => Finch.renderInBackground("background-many", { n })));
+    results.forEach((res, n) => assert.strictEqual(res, Finch.compile("background-many", { n })));
+});